@@ -0,0 +1,128 @@
+use crate::ServerError;
+use std::collections::HashMap;
+
+// A vote's `choice`, as stored by the hub. Its format depends on the proposal type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    Single(usize),                 // `single-choice` and `basic` proposals: `2`
+    Approval(Vec<usize>),          // `approval` proposals: `[1, 3]`
+    Ranked(Vec<usize>),            // `ranked-choice` proposals, most preferred first: `[2, 1, 3]`
+    Weighted(HashMap<usize, f64>), // `weighted` and `quadratic` proposals: `{"1": 2, "3": 1}`
+}
+
+impl Choice {
+    pub fn parse(proposal_type: &str, choice: &str) -> Result<Self, ServerError> {
+        let parsed = match proposal_type {
+            "single-choice" | "basic" => choice.trim().parse().ok().map(Choice::Single),
+            "approval" => serde_json::from_str(choice).ok().map(Choice::Approval),
+            "ranked-choice" => serde_json::from_str(choice).ok().map(Choice::Ranked),
+            "weighted" | "quadratic" => serde_json::from_str::<HashMap<String, f64>>(choice)
+                .ok()
+                .and_then(|weights| {
+                    weights
+                        .into_iter()
+                        .map(|(c, w)| c.parse().ok().map(|c| (c, w)))
+                        .collect::<Option<HashMap<usize, f64>>>()
+                })
+                .map(Choice::Weighted),
+            _ => {
+                return Err(ServerError::ErrorString(format!(
+                    "`{:}` proposals are not eligible for boosting",
+                    proposal_type
+                )))
+            }
+        };
+
+        parsed.ok_or_else(|| {
+            ServerError::ErrorString(format!(
+                "failed to parse choice `{}` for `{}` proposal",
+                choice, proposal_type
+            ))
+        })
+    }
+
+    // Returns the share of the vote that goes to the boosted `choices`, between 0 and 1.
+    // - single-choice: 1 if the voter voted for one of the boosted choices
+    // - approval: 1 if the voter approved one of the boosted choices
+    // - ranked-choice: 1 if the voter ranked one of the boosted choices first
+    // - weighted / quadratic: the part of the voter's weight allocated to the boosted choices
+    pub fn share_for(&self, choices: &[usize]) -> f64 {
+        let eligible = match self {
+            Choice::Single(choice) => choices.contains(choice),
+            Choice::Approval(approved) => approved.iter().any(|c| choices.contains(c)),
+            Choice::Ranked(ranking) => ranking.first().is_some_and(|c| choices.contains(c)),
+            Choice::Weighted(weights) => {
+                let total: f64 = weights.values().sum();
+                if total <= 0.0 {
+                    return 0.0;
+                }
+                let boosted: f64 = weights
+                    .iter()
+                    .filter(|(c, _)| choices.contains(c))
+                    .map(|(_, w)| w)
+                    .sum();
+                return boosted / total;
+            }
+        };
+
+        if eligible {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Whether the hub's `choice` column holds a plain integer for this proposal type, which can be filtered on in SQL.
+pub fn is_single_choice(proposal_type: &str) -> bool {
+    proposal_type == "single-choice" || proposal_type == "basic"
+}
+
+#[cfg(test)]
+mod test_choice {
+    use super::Choice;
+
+    #[test]
+    fn test_single_choice() {
+        let choice = Choice::parse("basic", "2").unwrap();
+        assert_eq!(choice, Choice::Single(2));
+        assert_eq!(choice.share_for(&[2]), 1.0);
+        assert_eq!(choice.share_for(&[1]), 0.0);
+        assert_eq!(choice.share_for(&[1, 2]), 1.0);
+    }
+
+    #[test]
+    fn test_approval() {
+        let choice = Choice::parse("approval", "[1, 3]").unwrap();
+        assert_eq!(choice, Choice::Approval(vec![1, 3]));
+        assert_eq!(choice.share_for(&[3]), 1.0);
+        assert_eq!(choice.share_for(&[2]), 0.0);
+    }
+
+    #[test]
+    fn test_ranked_choice() {
+        let choice = Choice::parse("ranked-choice", "[2,1,3]").unwrap();
+        assert_eq!(choice.share_for(&[2]), 1.0);
+        // Ranked, but not first
+        assert_eq!(choice.share_for(&[1]), 0.0);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let choice = Choice::parse("weighted", r#"{"1": 3, "3": 1}"#).unwrap();
+        assert_eq!(choice.share_for(&[1]), 0.75);
+        assert_eq!(choice.share_for(&[3]), 0.25);
+        assert_eq!(choice.share_for(&[2]), 0.0);
+        assert_eq!(choice.share_for(&[1, 3]), 1.0);
+
+        let choice = Choice::parse("quadratic", r#"{"2": 10}"#).unwrap();
+        assert_eq!(choice.share_for(&[2]), 1.0);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Choice::parse("single-choice", "[1]").is_err());
+        assert!(Choice::parse("weighted", r#"{"a": 1}"#).is_err());
+        assert!(Choice::parse("custom", "1").is_err());
+    }
+}
@@ -0,0 +1,406 @@
+use crate::ServerError;
+use cached::proc_macro::cached;
+use cached::SizedCache;
+use ethers::types::{Address, U256};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Where the delegation snapshots used by `delegator-passthrough` boosts come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DelegationSource {
+    // A JSON file mapping proposal ids to delegates to their delegations:
+    // `{ "<proposal_id>": { "<delegate>": [{ "delegator": "0x...", "vp": 12.5 }] } }`
+    File(PathBuf),
+    // An API answering `GET <url>?proposal=<proposal_id>` with the delegations of every delegate:
+    // `{ "<delegate>": [{ "delegator": "0x...", "vp": 12.5 }] }`
+    Api(String),
+}
+
+impl TryFrom<&str> for DelegationSource {
+    type Error = &'static str;
+
+    // Either `file:<path>`, or the url of the API
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some(path) = value.strip_prefix("file:") {
+            Ok(DelegationSource::File(PathBuf::from(path)))
+        } else if value.starts_with("http://") || value.starts_with("https://") {
+            Ok(DelegationSource::Api(value.to_string()))
+        } else {
+            Err("invalid delegation source")
+        }
+    }
+}
+
+// The voting power a delegator delegated to a delegate, at the proposal's snapshot.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Delegation {
+    pub delegator: Address,
+    #[serde(rename = "vp")]
+    pub voting_power: f64,
+}
+
+// The delegations of every delegate
+pub type Delegations = HashMap<Address, Vec<Delegation>>;
+
+impl DelegationSource {
+    pub async fn get_delegations(
+        &self,
+        client: &reqwest::Client,
+        proposal_id: &str,
+    ) -> Result<Delegations, ServerError> {
+        match self {
+            DelegationSource::File(path) => Ok(read_snapshots(path.clone())
+                .await?
+                .get(proposal_id)
+                .cloned()
+                .unwrap_or_default()),
+            DelegationSource::Api(url) => {
+                let delegations = client
+                    .get(url)
+                    .query(&[("proposal", proposal_id)])
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(delegations)
+            }
+        }
+    }
+}
+
+// The snapshot file is only read once: the guard needs to be restarted to pick up a new file.
+#[cached(
+    result = true,
+    sync_writes = true,
+    type = "SizedCache<PathBuf, Arc<HashMap<String, Delegations>>>",
+    create = "{ SizedCache::with_size(10) }"
+)]
+async fn read_snapshots(path: PathBuf) -> Result<Arc<HashMap<String, Delegations>>, ServerError> {
+    let content = tokio::fs::read_to_string(&path).await?;
+    Ok(Arc::new(serde_json::from_str(&content)?))
+}
+
+// Splits the reward of a delegate across their delegators, pro rata of the voting power they delegated.
+// The delegate keeps the part of the reward corresponding to their own voting power, along with the rounding dust,
+// so the split always adds up to `reward`.
+pub fn split_reward(
+    reward: U256,
+    delegate: Address,
+    delegate_voting_power: f64,
+    delegations: &[Delegation],
+    decimals: u8,
+) -> Result<Vec<(Address, U256)>, ServerError> {
+    // The voting powers come from different sources, allow them to differ by rounding errors
+    let delegated: f64 = delegations.iter().map(|d| d.voting_power).sum();
+    if delegations.iter().any(|d| d.voting_power < 0.0)
+        || delegated > delegate_voting_power * (1.0 + DELEGATION_TOLERANCE)
+    {
+        return Err(ServerError::ErrorString(format!(
+            "delegations ({}) do not match the voting power of the delegate ({})",
+            delegated, delegate_voting_power
+        )));
+    }
+
+    let pow = 10f64.powi(decimals as i32);
+    let voting_powers: Vec<U256> = delegations
+        .iter()
+        .map(|d| U256::from((d.voting_power * pow) as u128))
+        .collect();
+    // Within the tolerance, the delegations may add up to slightly more than the delegate's voting power
+    let total = std::cmp::max(
+        U256::from((delegate_voting_power * pow) as u128),
+        voting_powers.iter().fold(U256::zero(), |acc, vp| acc + vp),
+    );
+    if total.is_zero() {
+        return Ok(vec![(delegate, reward)]);
+    }
+
+    let mut rewards: Vec<(Address, U256)> = Vec::with_capacity(delegations.len() + 1);
+    let mut distributed = U256::zero();
+    for (delegation, voting_power) in delegations.iter().zip(voting_powers) {
+        let share = reward * voting_power / total;
+        if share.is_zero() {
+            continue;
+        }
+        distributed += share;
+        // A delegator may appear more than once in the snapshot
+        match rewards.iter_mut().find(|(a, _)| *a == delegation.delegator) {
+            Some((_, amount)) => *amount += share,
+            None => rewards.push((delegation.delegator, share)),
+        }
+    }
+
+    // `distributed <= reward` because every share is rounded down and the delegations add up to at most `total`
+    let own_share = reward - distributed;
+    if !own_share.is_zero() {
+        match rewards.iter_mut().find(|(a, _)| *a == delegate) {
+            Some((_, amount)) => *amount += own_share,
+            None => rewards.insert(0, (delegate, own_share)),
+        }
+    }
+
+    Ok(rewards)
+}
+
+// Relative difference allowed between the delegated voting power and the voting power of the delegate
+const DELEGATION_TOLERANCE: f64 = 1e-9;
+
+// The rewards of a `delegator-passthrough` boost, once every reward is split across the voter's delegators.
+#[derive(Debug, Clone, Default)]
+pub struct Passthrough {
+    pub recipients: HashMap<Address, Vec<Address>>, // The recipients of the reward of every voter
+    pub rewards: HashMap<Address, U256>, // The total amount of every recipient, over every voter they delegated to
+}
+
+// Splits the reward of every voter across their delegators, see `split_reward`, and adds up the amounts per recipient.
+// A delegator with several delegates, or who also voted, must get a single voucher for the whole amount.
+// Delegators for which `eligible` returns false (e.g. excluded voters) get nothing: the delegate keeps their share.
+pub fn pass_through(
+    rewards: &HashMap<Address, U256>,
+    voting_powers: &HashMap<Address, f64>,
+    delegations: &Delegations,
+    decimals: u8,
+    eligible: impl Fn(&Delegation) -> bool,
+) -> Result<Passthrough, ServerError> {
+    let mut passthrough = Passthrough::default();
+    for (voter, reward) in rewards {
+        let voting_power = *voting_powers
+            .get(voter)
+            .ok_or("could not find the voting power of a voter")?;
+        // The voting power of the ineligible delegations still counts, so their share is part of the delegate's own share
+        let delegations: Vec<Delegation> = delegations
+            .get(voter)
+            .into_iter()
+            .flatten()
+            .filter(|delegation| eligible(delegation))
+            .cloned()
+            .collect();
+
+        let split = split_reward(*reward, *voter, voting_power, &delegations, decimals)?;
+        let mut recipients = Vec::with_capacity(split.len());
+        for (recipient, amount) in split {
+            *passthrough.rewards.entry(recipient).or_default() += amount;
+            recipients.push(recipient);
+        }
+        passthrough.recipients.insert(*voter, recipients);
+    }
+    Ok(passthrough)
+}
+
+#[cfg(test)]
+mod test_delegation {
+    use super::{pass_through, split_reward, Delegation, DelegationSource};
+    use axum::extract::Query;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use ethers::types::{Address, U256};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_split_reward() {
+        let delegate = Address::random();
+        let delegations = [
+            Delegation {
+                delegator: Address::random(),
+                voting_power: 50.0,
+            },
+            Delegation {
+                delegator: Address::random(),
+                voting_power: 30.0,
+            },
+        ];
+
+        let rewards = split_reward(U256::from(1001), delegate, 100.0, &delegations, 18).unwrap();
+        assert_eq!(
+            rewards,
+            [
+                (delegate, U256::from(201)),
+                (delegations[0].delegator, U256::from(500)),
+                (delegations[1].delegator, U256::from(300)),
+            ]
+        );
+
+        // Delegations can't exceed the voting power of the delegate
+        assert!(split_reward(U256::from(1000), delegate, 70.0, &delegations, 18).is_err());
+    }
+
+    #[test]
+    fn test_split_reward_rounding() {
+        let delegate = Address::random();
+        let delegations = [
+            Delegation {
+                delegator: Address::random(),
+                voting_power: 0.1,
+            },
+            Delegation {
+                delegator: Address::random(),
+                voting_power: 0.2,
+            },
+        ];
+
+        // 0.1 + 0.2 > 0.3, only because of the floating point representation
+        assert!(delegations[0].voting_power + delegations[1].voting_power > 0.3);
+        let rewards = split_reward(U256::from(300), delegate, 0.3, &delegations, 18).unwrap();
+        let total = rewards
+            .iter()
+            .fold(U256::zero(), |acc, (_, reward)| acc + reward);
+        assert_eq!(total, U256::from(300));
+    }
+
+    #[test]
+    fn test_pass_through() {
+        let (alice, bob, carol) = (Address::random(), Address::random(), Address::random());
+        // Carol delegated to both Alice and Bob
+        let delegations = HashMap::from([
+            (
+                alice,
+                vec![Delegation {
+                    delegator: carol,
+                    voting_power: 50.0,
+                }],
+            ),
+            (
+                bob,
+                vec![Delegation {
+                    delegator: carol,
+                    voting_power: 10.0,
+                }],
+            ),
+        ]);
+        let voting_powers = HashMap::from([(alice, 100.0), (bob, 20.0)]);
+        let rewards = HashMap::from([(alice, U256::from(1000)), (bob, U256::from(200))]);
+
+        let passthrough =
+            pass_through(&rewards, &voting_powers, &delegations, 18, |_| true).unwrap();
+        assert_eq!(passthrough.rewards[&alice], U256::from(500));
+        assert_eq!(passthrough.rewards[&bob], U256::from(100));
+        // A single amount for Carol, whichever delegate asks for the vouchers
+        assert_eq!(passthrough.rewards[&carol], U256::from(600));
+        assert!(passthrough.recipients[&alice].contains(&carol));
+        assert!(passthrough.recipients[&bob].contains(&carol));
+        assert_eq!(
+            passthrough
+                .rewards
+                .values()
+                .fold(U256::zero(), |acc, r| acc + r),
+            U256::from(1200)
+        );
+    }
+
+    #[test]
+    fn test_pass_through_ineligible_delegator() {
+        let (alice, bob, carol) = (Address::random(), Address::random(), Address::random());
+        let delegations = HashMap::from([(
+            alice,
+            vec![
+                Delegation {
+                    delegator: bob,
+                    voting_power: 30.0,
+                },
+                Delegation {
+                    delegator: carol,
+                    voting_power: 50.0,
+                },
+            ],
+        )]);
+        let voting_powers = HashMap::from([(alice, 100.0)]);
+        let rewards = HashMap::from([(alice, U256::from(1000))]);
+
+        // Carol is excluded from the boost, Alice keeps her share
+        let passthrough = pass_through(&rewards, &voting_powers, &delegations, 18, |d| {
+            d.delegator != carol
+        })
+        .unwrap();
+        assert_eq!(passthrough.rewards[&alice], U256::from(700));
+        assert_eq!(passthrough.rewards[&bob], U256::from(300));
+        assert!(!passthrough.rewards.contains_key(&carol));
+        assert_eq!(passthrough.recipients[&alice], [alice, bob]);
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            DelegationSource::try_from("file:/tmp/delegations.json"),
+            Ok(DelegationSource::File("/tmp/delegations.json".into()))
+        );
+        assert_eq!(
+            DelegationSource::try_from("https://example.com/delegations"),
+            Ok(DelegationSource::Api(
+                "https://example.com/delegations".to_string()
+            ))
+        );
+        assert!(DelegationSource::try_from("/tmp/delegations.json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_source() {
+        let delegate = Address::random();
+        let delegator = Address::random();
+        let path = std::env::temp_dir().join(format!("delegations-{:?}.json", delegate));
+        let content = json!({
+            "0x1234": {
+                format!("{:?}", delegate): [{ "delegator": format!("{:?}", delegator), "vp": 12.5 }]
+            }
+        });
+        std::fs::write(&path, content.to_string()).unwrap();
+
+        let source = DelegationSource::File(path.clone());
+        let client = reqwest::Client::new();
+        let delegations = source.get_delegations(&client, "0x1234").await.unwrap();
+        assert_eq!(
+            delegations[&delegate],
+            [Delegation {
+                delegator,
+                voting_power: 12.5
+            }]
+        );
+
+        // The file is only read once
+        std::fs::remove_file(path).unwrap();
+        let delegations = source.get_delegations(&client, "0x1234").await.unwrap();
+        assert_eq!(delegations.len(), 1);
+
+        // Unknown proposals did not get any delegation
+        let delegations = source.get_delegations(&client, "0x5678").await.unwrap();
+        assert!(delegations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_source() {
+        let delegate = Address::random();
+        let delegator = Address::random();
+        let app = Router::new().route(
+            "/delegations",
+            get(
+                move |Query(params): Query<HashMap<String, String>>| async move {
+                    let delegations = if params["proposal"] == "0x1234" {
+                        json!({
+                            format!("{:?}", delegate): [{ "delegator": format!("{:?}", delegator), "vp": 3.0 }]
+                        })
+                    } else {
+                        json!({})
+                    };
+                    Json::<Value>(delegations)
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/delegations", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let source = DelegationSource::try_from(url.as_str()).unwrap();
+        let client = reqwest::Client::new();
+        let delegations = source.get_delegations(&client, "0x1234").await.unwrap();
+        assert_eq!(
+            delegations[&delegate],
+            [Delegation {
+                delegator,
+                voting_power: 3.0
+            }]
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use crate::ServerError;
+use ethers::types::{Address, U256};
+use std::collections::HashMap;
+
+// What to do with the tokens left in the pool once every eligible voter received their share.
+// Integer divisions always round down, so a small remainder ("dust") is expected for every distribution type.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum RemainderPolicy {
+    #[default]
+    Leftover, // The remainder stays in the boost, and can be withdrawn by the boost owner
+    LargestVoter, // The remainder goes to the voter with the highest voting power
+    RoundRobin, // The remainder is given one wei at a time, starting from the highest voting power
+}
+
+impl TryFrom<&str> for RemainderPolicy {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "leftover" => Ok(RemainderPolicy::Leftover),
+            "largest-voter" => Ok(RemainderPolicy::LargestVoter),
+            "round-robin" => Ok(RemainderPolicy::RoundRobin),
+            _ => Err("invalid remainder policy"),
+        }
+    }
+}
+
+impl std::fmt::Display for RemainderPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RemainderPolicy::Leftover => "leftover",
+            RemainderPolicy::LargestVoter => "largest-voter",
+            RemainderPolicy::RoundRobin => "round-robin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// The full outcome of a boost: what every eligible voter gets, and what is left in the pool.
+// A `Distribution` can only be built through `Distribution::new`, which guarantees that `total + leftover == pool_size`.
+#[derive(Debug, Clone, Default)]
+pub struct Distribution {
+    pub rewards: HashMap<Address, U256>,
+    pub total: U256,
+    pub leftover: U256,
+}
+
+impl Distribution {
+    // Builds the distribution and applies the remainder `policy`.
+    // `rewards` must be ordered by priority (highest voting power first), this is the order used by the remainder policy.
+    // If `limit` is set, the remainder policy will never push a voter's reward above it.
+    pub fn new(
+        mut rewards: Vec<(Address, U256)>,
+        pool_size: U256,
+        policy: RemainderPolicy,
+        limit: Option<U256>,
+    ) -> Result<Self, ServerError> {
+        let total = sum_rewards(&rewards)?;
+        check_pool_conservation(total, pool_size)?;
+
+        apply_remainder_policy(&mut rewards, pool_size - total, policy, limit);
+
+        let total = sum_rewards(&rewards)?;
+        check_pool_conservation(total, pool_size)?;
+
+        Ok(Self {
+            rewards: rewards.into_iter().collect(),
+            total,
+            leftover: pool_size - total,
+        })
+    }
+}
+
+// Refuses any distribution that would hand out more tokens than what the pool holds.
+pub fn check_pool_conservation(total: U256, pool_size: U256) -> Result<(), ServerError> {
+    if total > pool_size {
+        tracing::error!(?total, ?pool_size, "distribution exceeds pool size");
+        Err(ServerError::ErrorString(format!(
+            "distribution exceeds pool size: {} > {}",
+            total, pool_size
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+fn sum_rewards(rewards: &[(Address, U256)]) -> Result<U256, ServerError> {
+    rewards.iter().try_fold(U256::zero(), |acc, (_, reward)| {
+        acc.checked_add(*reward)
+            .ok_or_else(|| ServerError::ErrorString("overflow when summing rewards".to_string()))
+    })
+}
+
+// Only rounding dust is meant to be redistributed: every voter gets at most one extra wei with `RoundRobin`,
+// and voters that already reached `limit` are skipped. Whatever could not be redistributed stays as leftover.
+fn apply_remainder_policy(
+    rewards: &mut [(Address, U256)],
+    mut remainder: U256,
+    policy: RemainderPolicy,
+    limit: Option<U256>,
+) {
+    let headroom = |reward: U256| match limit {
+        Some(limit) => limit.saturating_sub(reward),
+        None => U256::MAX - reward,
+    };
+
+    match policy {
+        RemainderPolicy::Leftover => (),
+        RemainderPolicy::LargestVoter => {
+            if let Some((_, reward)) = rewards.iter_mut().find(|(_, r)| !headroom(*r).is_zero()) {
+                let extra = std::cmp::min(remainder, headroom(*reward));
+                *reward += extra;
+            }
+        }
+        RemainderPolicy::RoundRobin => {
+            for (_, reward) in rewards.iter_mut() {
+                if remainder.is_zero() {
+                    break;
+                }
+                if !headroom(*reward).is_zero() {
+                    *reward += U256::one();
+                    remainder -= U256::one();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_distribution {
+    use super::{check_pool_conservation, Distribution, RemainderPolicy};
+    use ethers::types::{Address, U256};
+
+    fn even_split(voters: &[Address], pool_size: U256) -> Vec<(Address, U256)> {
+        let prize = pool_size / voters.len();
+        voters.iter().map(|v| (*v, prize)).collect()
+    }
+
+    #[test]
+    fn test_leftover() {
+        let voters = [Address::random(), Address::random(), Address::random()];
+        let pool_size = U256::from(100);
+
+        let distribution = Distribution::new(
+            even_split(&voters, pool_size),
+            pool_size,
+            RemainderPolicy::Leftover,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(distribution.total, U256::from(99));
+        assert_eq!(distribution.leftover, U256::from(1));
+        assert!(voters
+            .iter()
+            .all(|v| distribution.rewards[v] == U256::from(33)));
+    }
+
+    #[test]
+    fn test_largest_voter() {
+        let voters = [Address::random(), Address::random(), Address::random()];
+        let pool_size = U256::from(101);
+
+        let distribution = Distribution::new(
+            even_split(&voters, pool_size),
+            pool_size,
+            RemainderPolicy::LargestVoter,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(distribution.total, pool_size);
+        assert_eq!(distribution.leftover, U256::zero());
+        assert_eq!(distribution.rewards[&voters[0]], U256::from(35));
+        assert_eq!(distribution.rewards[&voters[1]], U256::from(33));
+        assert_eq!(distribution.rewards[&voters[2]], U256::from(33));
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let voters = [Address::random(), Address::random(), Address::random()];
+        let pool_size = U256::from(101);
+
+        let distribution = Distribution::new(
+            even_split(&voters, pool_size),
+            pool_size,
+            RemainderPolicy::RoundRobin,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(distribution.total, pool_size);
+        assert_eq!(distribution.rewards[&voters[0]], U256::from(34));
+        assert_eq!(distribution.rewards[&voters[1]], U256::from(34));
+        assert_eq!(distribution.rewards[&voters[2]], U256::from(33));
+    }
+
+    #[test]
+    fn test_remainder_respects_limit() {
+        let voters = [Address::random(), Address::random()];
+        let pool_size = U256::from(100);
+        let limit = U256::from(40);
+        let rewards = vec![(voters[0], limit), (voters[1], U256::from(39))];
+
+        let distribution = Distribution::new(
+            rewards,
+            pool_size,
+            RemainderPolicy::LargestVoter,
+            Some(limit),
+        )
+        .unwrap();
+
+        // First voter is capped, so the second one gets what fits under the limit.
+        assert_eq!(distribution.rewards[&voters[0]], limit);
+        assert_eq!(distribution.rewards[&voters[1]], limit);
+        assert_eq!(distribution.leftover, U256::from(20));
+    }
+
+    #[test]
+    fn test_exceeds_pool() {
+        let rewards = vec![
+            (Address::random(), U256::from(60)),
+            (Address::random(), U256::from(60)),
+        ];
+
+        assert!(
+            Distribution::new(rewards, U256::from(100), RemainderPolicy::Leftover, None).is_err()
+        );
+        assert!(check_pool_conservation(U256::from(101), U256::from(100)).is_err());
+        assert!(check_pool_conservation(U256::from(100), U256::from(100)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            RemainderPolicy::try_from("round-robin").unwrap(),
+            RemainderPolicy::RoundRobin
+        );
+        assert!(RemainderPolicy::try_from("random").is_err());
+    }
+}
@@ -1,11 +1,20 @@
 use axum::response::{IntoResponse, Response};
+use delegation::DelegationSource;
 use ethers::types::Address;
 use hyper::http::StatusCode;
+use randomness::{BeaconNode, Beaconchain, RandomnessSource};
 use std::collections::HashSet;
 
+pub mod choice;
+pub mod delegation;
+pub mod distribution;
+pub mod exclusions;
 pub mod lottery;
+pub mod randomness;
 pub mod routes;
 pub mod signatures;
+pub mod space;
+pub mod strategy;
 pub mod tokens;
 
 use std::env;
@@ -61,14 +70,25 @@ lazy_static! {
         env::var("EPOCH_URL").expect("Please add EPOCH_URL to your environment or .env file");
     static ref SLOT_URL: String =
         env::var("SLOT_URL").expect("Please add SLOT_URL to your environment or .env file");
+    // Your own beacon node if `BEACON_NODE_URL` is set, beaconcha.in otherwise
+    static ref RANDOMNESS_SOURCE: Box<dyn RandomnessSource> = match env::var("BEACON_NODE_URL") {
+        Ok(url) => Box::new(BeaconNode::new(&url)),
+        Err(_) => Box::new(Beaconchain::new(&SLOT_URL, &EPOCH_URL, &BEACONCHAIN_API_KEY)),
+    };
     static ref DISABLED_TOKENS: HashSet<(Address, &'static str)> =
         tokens::create_disabled_token_list();
+    static ref DELEGATION_SOURCE: Option<DelegationSource> = env::var("DELEGATION_SOURCE")
+        .ok()
+        .map(|source| DelegationSource::try_from(source.as_str())
+            .expect("invalid DELEGATION_SOURCE"));
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum ServerError {
     ErrorString(String),
     ProposalStillInProgress,
+    BoostRefundable(String),
+    BoostNotTriggered(String),
 }
 
 impl<T: std::string::ToString + Sized> From<T> for ServerError {
@@ -88,6 +108,16 @@ impl IntoResponse for ServerError {
                 "Proposal has not ended yet",
             )
                 .into_response(),
+            ServerError::BoostRefundable(reason) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Boost is refundable: {}", reason),
+            )
+                .into_response(),
+            ServerError::BoostNotTriggered(reason) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Boost not triggered: {}", reason),
+            )
+                .into_response(),
         }
     }
 }
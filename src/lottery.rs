@@ -1,87 +1,516 @@
-use crate::routes::{BoostInfo, ProposalInfo, Vote};
-use crate::{ServerError, BEACONCHAIN_API_KEY};
-use crate::{EPOCH_URL, MYRIAD, SLOT_URL};
+use crate::distribution::{Distribution, RemainderPolicy};
+use crate::randomness::{is_seed_slot, seed_slot, Randao, RandomnessParams};
+use crate::routes::{get_votes, BoostInfo, ProposalInfo, Vote};
+use crate::strategy::BoostStrategy;
+use crate::{ServerError, MYRIAD, RANDOMNESS_SOURCE};
 use cached::proc_macro::cached;
 use cached::TimedSizedCache;
 use durations::WEEK;
 use ethers::types::{Address, U256};
-use mysql_async::prelude::Queryable;
 use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-use std::str::FromStr;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+// The outcome of a lottery, along with the proof of the draw if one was needed.
+#[derive(Debug, Clone)]
+pub struct Lottery {
+    pub distribution: Distribution,
+    pub ranking: Vec<Address>, // The winners in draw order, or by voting power if every voter won without a draw
+    pub proof: Option<LotteryProof>,
+}
+
+// How the voting power of a voter translates into their chances of winning.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LotteryWeighting {
+    Uniform, // One ticket per voter, every voter has the same chances
+    #[default]
+    Vp, // Chances are proportional to the voting power
+    SqrtVp, // Chances are proportional to the square root of the voting power, which favors smaller voters
+}
+
+impl TryFrom<&str> for LotteryWeighting {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "uniform" => Ok(LotteryWeighting::Uniform),
+            "vp" => Ok(LotteryWeighting::Vp),
+            "sqrt-vp" => Ok(LotteryWeighting::SqrtVp),
+            _ => Err("invalid lottery weighting"),
+        }
+    }
+}
+
+impl LotteryWeighting {
+    // Every transformation is monotonic, so votes sorted by voting power stay sorted by weight
+    pub fn weight(&self, voting_power: f64) -> f64 {
+        match self {
+            LotteryWeighting::Uniform => 1.0,
+            LotteryWeighting::Vp => voting_power,
+            LotteryWeighting::SqrtVp => voting_power.max(0.0).sqrt(),
+        }
+    }
+}
+
+// Prizes of a lottery, by tier: the first winners drawn get the prizes of the first tier, and so on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrizeTiers {
+    pub tiers: Vec<PrizeTier>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PrizeTier {
+    pub num_winners: u32,
+    pub share: u16, // Share of the pool for the whole tier, in base `10_000`, split evenly across its winners
+}
+
+impl PrizeTiers {
+    // Every tier is formatted as `num_winners:share`, e.g. `["1:5000", "5:3000", "50:2000"]` for 1 grand prize of half
+    // the pool, 5 medium prizes of 6% and 50 small prizes of 0.4%.
+    pub fn parse(tiers: Option<&[String]>) -> Result<Option<Self>, &'static str> {
+        let tiers = match tiers {
+            None | Some([]) => return Ok(None),
+            Some(tiers) => tiers
+                .iter()
+                .map(|tier| {
+                    let (num_winners, share) = tier.split_once(':').ok_or("invalid prize tier")?;
+                    let num_winners: u32 = num_winners
+                        .parse()
+                        .map_err(|_| "failed to parse prize tier winners")?;
+                    let share: u16 = share
+                        .parse()
+                        .map_err(|_| "failed to parse prize tier share")?;
+                    if num_winners == 0 {
+                        return Err("invalid number of winners in prize tier: 0");
+                    }
+                    Ok(PrizeTier { num_winners, share })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        let total_share: u32 = tiers.iter().map(|t| t.share as u32).sum();
+        if total_share > MYRIAD as u32 {
+            return Err("prize tiers exceed the pool");
+        }
+
+        Ok(Some(Self { tiers }))
+    }
+
+    pub fn num_winners(&self) -> u32 {
+        self.tiers.iter().map(|t| t.num_winners).sum()
+    }
+
+    // The prize of every winner, in draw order
+    pub fn prizes(&self, pool_size: U256) -> Vec<U256> {
+        self.tiers
+            .iter()
+            .flat_map(|tier| {
+                let prize = pool_size * tier.share / MYRIAD / tier.num_winners;
+                std::iter::repeat_n(prize, tier.num_winners as usize)
+            })
+            .collect()
+    }
+
+    // Winners get the prize of their tier, in draw order. The remainder (the rounding dust of every tier, and the prizes
+    // nobody won) always stays in the pool: any other remainder policy would favor the first winner drawn rather than
+    // the biggest voter.
+    pub fn distribution(
+        &self,
+        winners: &[Address],
+        pool_size: U256,
+    ) -> Result<Distribution, ServerError> {
+        let rewards = winners
+            .iter()
+            .copied()
+            .zip(self.prizes(pool_size))
+            .collect();
+        Distribution::new(rewards, pool_size, RemainderPolicy::Leftover, None)
+    }
+}
+
+// How the lotteries of a strategy version are drawn, see `REGISTRY`. The rules of a version never change, so that a draw
+// can always be checked against the version recorded in its proof.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct LotteryRules {
+    pub sampling: Sampling,
+    pub randomness: RandomnessParams,
+    pub canonical_order: bool, // Whether candidates are sorted by `canonical_order`, rather than fetched
+}
+
+impl LotteryRules {
+    // The rules of a version of the proposal strategy
+    pub fn of_version(version: &str) -> Result<Self, ServerError> {
+        let strategy = BoostStrategy::try_from(("proposal", version))?;
+        Ok(strategy
+            .lottery_rules()
+            .ok_or("strategy version has no lotteries")?)
+    }
+}
+
+// Everything needed to reproduce a draw, see `LotteryProof::replay`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotteryProof {
+    pub slot: u64, // The slot whose randao reveal seeded the draw, the first proposed one after the delay
+    #[serde(default)]
+    pub missed_slots: Vec<u64>, // The slots from the end of the delay that had no block, see `is_seed_slot`
+    pub epoch: u64, // The epoch of that slot
+    pub proposal_id: String,
+    pub proposal_end: u64, // Timestamp of the end of the proposal, from which the slot is picked
+    pub boost_id: String,
+    pub chain_id: String,
+    pub version: String, // The version of the boost's strategy, which sets the rules of the draw, see `LotteryRules`
+    #[serde(default)]
+    pub delay_epochs: u64, // How long after the end of the proposal the slot was picked, see `RandomnessParams`
+    pub randao_reveal: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>, // Mixed with the randao reveal, so that every boost gets its own seed
+    pub seed: String, // SHA-256 of the randao reveal, followed by the salt if any
+    pub num_winners: u32,
+    pub limit: Option<u16>,
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub weighting: LotteryWeighting,
+    pub decimals: u8,
+    pub score: f64,                 // Score of the proposal, used to enforce the limit
+    pub candidates: Vec<Candidate>, // The eligible votes, in the order of the draw, with their voting power
+    pub candidates_hash: String, // Hash of the candidates once weighted and limited, see `hash_candidates`
+    pub winners: Vec<Address>,   // In draw order
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub voter: Address,
+    pub weight: f64,
+}
+
+// Where the seed of a draw comes from, recorded in its proof so that anyone can check the seed belongs to the boost.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedParams {
+    pub proposal_id: String,
+    pub proposal_end: u64,
+    pub boost_id: String,
+    pub chain_id: String,
+    pub version: String,
+    pub randomness: RandomnessParams, // Set by the version, see `LotteryRules`
+}
+
+impl SeedParams {
+    pub fn new(boost_info: &BoostInfo, proposal_info: &ProposalInfo) -> Self {
+        Self {
+            proposal_id: proposal_info.id.clone(),
+            proposal_end: proposal_info.end,
+            boost_id: boost_info.id.to_string(),
+            chain_id: boost_info.chain_id.to_string(),
+            version: boost_info.strategy.version().to_string(),
+            randomness: boost_info.params.lottery_rules.randomness,
+        }
+    }
+
+    // The first slot that may seed the draw
+    pub fn slot(&self) -> u64 {
+        seed_slot(self.proposal_end, self.randomness.delay_epochs)
+    }
+
+    pub fn salt(&self) -> Option<String> {
+        self.randomness
+            .independent
+            .then(|| boost_salt(&self.proposal_id, &self.chain_id, &self.boost_id))
+    }
+}
+
+fn boost_salt(proposal_id: &str, chain_id: &str, boost_id: &str) -> String {
+    format!("{}:{}:{}", proposal_id, chain_id, boost_id)
+}
+
+impl LotteryProof {
+    // Reruns the draw from the proof alone and returns the winners, in draw order.
+    // Fails if the slot, the seed or the candidates don't match what the proof commits to, or if the draw doesn't follow
+    // the rules of the strategy version recorded in the proof.
+    pub fn replay(&self) -> Result<Vec<Address>, ServerError> {
+        let rules = LotteryRules::of_version(&self.version)?;
+        if self.sampling != rules.sampling {
+            return Err("sampling does not match the strategy version".into());
+        }
+        if self.delay_epochs != rules.randomness.delay_epochs {
+            return Err("delay does not match the strategy version".into());
+        }
+        match (&self.salt, rules.randomness.independent) {
+            (None, true) => return Err("salt is missing".into()),
+            (Some(_), false) => return Err("strategy version does not salt the seed".into()),
+            (Some(salt), true) => {
+                if *salt != boost_salt(&self.proposal_id, &self.chain_id, &self.boost_id) {
+                    return Err("salt does not belong to the boost".into());
+                }
+            }
+            (None, false) => (),
+        }
+
+        if !is_seed_slot(
+            self.slot,
+            &self.missed_slots,
+            self.proposal_end,
+            self.delay_epochs,
+        ) {
+            return Err("slot is not the first proposed one after the end of the proposal".into());
+        }
+
+        let seed = seed_from_randao(&self.randao_reveal, self.salt.as_deref())?;
+        if format!("0x{}", hex::encode(seed)) != self.seed {
+            return Err("seed does not match the randao reveal".into());
+        }
+
+        if self.candidates.is_empty() {
+            return Err("proof has no candidates".into());
+        }
 
-const FIRST_MERGED_SLOT: u64 = 4700013;
-const FIRST_MERGED_SLOT_TIMESTAMP: u64 = 1663224179;
+        let mut votes = self
+            .candidates
+            .iter()
+            .map(|c| Vote {
+                voter: c.voter,
+                voting_power: c.weight,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        if rules.canonical_order
+            && votes
+                .windows(2)
+                .any(|w| canonical_cmp(&w[0], &w[1]) != Ordering::Less)
+        {
+            return Err("candidates are not in canonical order".into());
+        }
+        apply_weights(
+            &mut votes,
+            self.weighting,
+            self.limit,
+            self.decimals,
+            self.score,
+        )?;
+        if hash_candidates(&votes) != self.candidates_hash {
+            return Err("candidates do not match the candidates hash".into());
+        }
+
+        Ok(draw_winners(&votes, seed, self.num_winners, self.sampling))
+    }
+
+    // Whether replaying the draw gives the published winners, in the same order.
+    pub fn verify(&self) -> Result<bool, ServerError> {
+        Ok(self.replay()? == self.winners)
+    }
+}
+
+pub async fn cached_lottery_winners(
+    pool: &mysql_async::Pool,
+    boost_info: &BoostInfo,
+    proposal_info: &ProposalInfo,
+    num_winners: u32,
+    limit: Option<u16>,
+) -> Result<Distribution, ServerError> {
+    let lottery = cached_lottery(pool, boost_info, proposal_info, num_winners, limit).await?;
+    Ok(lottery.distribution)
+}
 
 // LRU cache that uses `boost_id` and `chain_id` as keys
 #[cached(
     result = true,
     sync_writes = true,
-    type = "TimedSizedCache<String, HashMap<Address, U256>>",
+    type = "TimedSizedCache<String, Lottery>",
     create = "{ TimedSizedCache::with_size_and_lifespan(500, 3 * WEEK.as_secs())}",
     convert = r#"{ format!("{}{}", boost_info.id, boost_info.chain_id) }"#
 )]
-pub async fn cached_lottery_winners(
+pub async fn cached_lottery(
     pool: &mysql_async::Pool,
     boost_info: &BoostInfo,
     proposal_info: &ProposalInfo,
     num_winners: u32,
     limit: Option<u16>,
-) -> Result<HashMap<Address, U256>, ServerError> {
-    let bribed_choice = proposal_info.get_bribed_choice(&boost_info.params.eligibility)?;
-
-    let choice_constraint = if let Some(choice) = bribed_choice {
-        format!("AND choice = {}", choice)
-    } else {
-        "".to_string()
-    };
-
-    let query = format!(
-        "SELECT voter, vp, choice
-        FROM votes
-        WHERE proposal = '{}'
-        {}
-        ORDER BY vp DESC;",
-        proposal_info.id, choice_constraint
-    );
-
-    let mut conn = pool.get_conn().await?;
-    let result: Vec<(String, f64, String)> = conn.query(query).await?;
-    conn.disconnect().await?;
+) -> Result<Lottery, ServerError> {
+    let policy = boost_info.params.remainder_policy;
+    let bribed_choices = proposal_info.get_eligible_choices(&boost_info.params)?;
+    let mut votes = get_votes(pool, boost_info, proposal_info, bribed_choices.as_deref()).await?;
+    // Earlier versions draw from the votes in the order of the database, which must not change for their boosts
+    if boost_info.params.lottery_rules.canonical_order {
+        canonical_order(&mut votes);
+    }
 
-    if result.is_empty() {
+    if votes.is_empty() {
         tracing::warn!("No votes found for proposal {}", proposal_info.id);
-        return Ok(HashMap::new());
+        return Ok(Lottery {
+            distribution: Distribution::new(vec![], boost_info.pool_size, policy, None)?,
+            ranking: vec![],
+            proof: None,
+        });
     }
 
-    let mut votes = result
-        .into_iter()
-        .map(|(voter, vp, _)| {
-            Ok(Vote {
-                voter: Address::from_str(&voter)?,
-                voting_power: vp,
-            })
-        })
-        .collect::<Result<Vec<Vote>, ServerError>>()?;
-
-    // If there are not enough voters, then every voter is eligible to the same reward
+    let tiers = boost_info.params.prize_tiers.as_ref();
+    let weighting = boost_info.params.lottery_weighting;
+    let mut num_draws = num_winners;
     if votes.len() <= num_winners as usize {
-        tracing::warn!("Not enough voters to enforce the limit");
-        let prize = boost_info.pool_size / votes.len() as u32;
-        return Ok(votes.into_iter().map(|v| (v.voter, prize)).collect());
+        match tiers {
+            // If there are not enough voters, then every voter is eligible to the same reward
+            None => {
+                tracing::warn!("Not enough voters to enforce the limit");
+                let prize = boost_info.pool_size / votes.len() as u32;
+                let rewards = votes.iter().map(|v| (v.voter, prize)).collect();
+                return Ok(Lottery {
+                    distribution: Distribution::new(rewards, boost_info.pool_size, policy, None)?,
+                    ranking: votes.into_iter().map(|v| v.voter).collect(),
+                    proof: None,
+                });
+            }
+            // Every voter wins, but the draw still decides who gets which prize. The prizes of the lowest tiers that
+            // nobody could win are left in the pool.
+            Some(_) => {
+                num_draws = votes
+                    .iter()
+                    .filter(|v| weighting.weight(v.voting_power) > 0.0)
+                    .count() as u32;
+            }
+        }
     }
 
+    let seed = SeedParams::new(boost_info, proposal_info);
+    let randao = RANDOMNESS_SOURCE.randao(seed.slot()).await?;
+    let proof = draw(
+        &votes,
+        randao,
+        &seed,
+        num_draws,
+        limit,
+        boost_info.params.lottery_rules.sampling,
+        weighting,
+        boost_info.decimals,
+        proposal_info.score,
+    )?;
+
+    let distribution = match tiers {
+        Some(tiers) => tiers.distribution(&proof.winners, boost_info.pool_size)?,
+        // Order the winners by voting power, so that the remainder policy favors the biggest voters
+        None => {
+            let prize = boost_info.pool_size / num_winners;
+            let winners: HashSet<&Address> = proof.winners.iter().collect();
+            let rewards = votes
+                .into_iter()
+                .filter(|v| winners.contains(&v.voter))
+                .map(|v| (v.voter, prize))
+                .collect();
+            Distribution::new(rewards, boost_info.pool_size, policy, None)?
+        }
+    };
+    Ok(Lottery {
+        distribution,
+        ranking: proof.winners.clone(),
+        proof: Some(proof),
+    })
+}
+
+// Draws the winners among `votes`, in their order, seeded by `randao` and the salt of `seed`, and records how to
+// reproduce the draw.
+#[allow(clippy::too_many_arguments)]
+pub fn draw(
+    votes: &[Vote],
+    randao: Randao,
+    seed: &SeedParams,
+    num_winners: u32,
+    limit: Option<u16>,
+    sampling: Sampling,
+    weighting: LotteryWeighting,
+    decimals: u8,
+    score: f64,
+) -> Result<LotteryProof, ServerError> {
+    let mut votes = votes.to_vec();
+    let candidates = votes
+        .iter()
+        .map(|v| Candidate {
+            voter: v.voter,
+            weight: v.voting_power,
+        })
+        .collect();
+
+    apply_weights(&mut votes, weighting, limit, decimals, score)?;
+
+    let salt = seed.salt();
+    let seed_bytes = seed_from_randao(&randao.reveal, salt.as_deref())?;
+    let winners = draw_winners(&votes, seed_bytes, num_winners, sampling);
+
+    Ok(LotteryProof {
+        slot: randao.slot,
+        missed_slots: randao.missed_slots,
+        epoch: randao.epoch,
+        proposal_id: seed.proposal_id.clone(),
+        proposal_end: seed.proposal_end,
+        boost_id: seed.boost_id.clone(),
+        chain_id: seed.chain_id.clone(),
+        version: seed.version.clone(),
+        delay_epochs: seed.randomness.delay_epochs,
+        randao_reveal: randao.reveal,
+        salt,
+        seed: format!("0x{}", hex::encode(seed_bytes)),
+        num_winners,
+        limit,
+        sampling,
+        weighting,
+        decimals,
+        score,
+        candidates,
+        candidates_hash: hash_candidates(&votes),
+        winners,
+    })
+}
+
+// Turns the voting power of the candidates into their weight in the draw, then enforces the limit.
+// `score` is the score of the proposal, which is only meaningful for vp-weighted lotteries: the limit of other
+// weightings is relative to the sum of the weights.
+fn apply_weights(
+    votes: &mut [Vote],
+    weighting: LotteryWeighting,
+    limit: Option<u16>,
+    decimals: u8,
+    score: f64,
+) -> Result<(), ServerError> {
+    let score = match weighting {
+        LotteryWeighting::Vp => score,
+        _ => {
+            votes
+                .iter_mut()
+                .for_each(|v| v.voting_power = weighting.weight(v.voting_power));
+            votes.iter().map(|v| v.voting_power).sum()
+        }
+    };
+
     if let Some(limit) = limit {
-        adjust_vote_weights(&mut votes, boost_info.decimals, proposal_info.score, limit)?;
+        adjust_vote_weights(votes, decimals, score, limit)?;
     }
+    Ok(())
+}
+
+// Sorts the candidates by decreasing voting power, then by increasing address.
+// The database returns votes with the same voting power in no particular order, so the draw must never depend on the
+// order in which the votes were fetched, otherwise two guards could draw different winners from the same seed.
+// Only applied since version 0.0.4, see `REGISTRY`.
+pub fn canonical_order(votes: &mut [Vote]) {
+    votes.sort_by(canonical_cmp);
+}
 
-    let prize = boost_info.pool_size / num_winners;
-    let seed = get_randao_reveal(proposal_info.end).await?;
+fn canonical_cmp(a: &Vote, b: &Vote) -> Ordering {
+    b.voting_power
+        .total_cmp(&a.voting_power)
+        .then(a.voter.cmp(&b.voter))
+}
 
-    Ok(draw_winners(votes, seed, num_winners, prize))
+// SHA-256 of the candidates, one `<address>:<weight>` line per candidate, in the order of the draw.
+// The weight is formatted as the shortest decimal representation that parses back to the same number.
+fn hash_candidates(votes: &[Vote]) -> String {
+    let mut hasher = Sha256::new();
+    for vote in votes {
+        hasher.update(format!("{:?}:{}\n", vote.voter, vote.voting_power));
+    }
+    format!("0x{}", hex::encode(hasher.finalize()))
 }
 
 // Adjust the voting power of the voters to respect the limit.
@@ -149,26 +578,58 @@ fn adjust_vote_weights(
     Ok(())
 }
 
+// How the winners are drawn from the weighted candidates.
+// The same seed gives different winners with different methods, so the method is tied to the version of the boost's
+// strategy (see `REGISTRY`) and recorded in the proof: the winners of a boost can always be drawn again, and a method
+// must never change once boosts have been created with it. Faster or fairer methods get a new variant and a new version.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sampling {
+    #[default]
+    Rejection, // Version 0.0.1, see `rejection_sampling`
+    Fenwick, // Version 0.0.2, see `fenwick_sampling`
+}
+
+// Returns the winners, in draw order.
 fn draw_winners(
-    votes: Vec<Vote>,
+    votes: &[Vote],
     seed: [u8; 32],
     num_winners: u32,
-    prize: U256,
-) -> HashMap<Address, U256> {
+    sampling: Sampling,
+) -> Vec<Address> {
+    match sampling {
+        Sampling::Rejection => rejection_sampling(votes, seed, num_winners),
+        Sampling::Fenwick => fenwick_sampling(votes, seed, num_winners),
+    }
+}
+
+// Draws with replacement, and draws again whenever a winner is drawn twice. Each draw is a linear search.
+// Candidates whose weight doesn't add to the cumulative weights (a weight of 0, or too small to register next to the
+// previous weights) can never be drawn, so there are at most as many winners as candidates that can.
+fn rejection_sampling(votes: &[Vote], seed: [u8; 32], num_winners: u32) -> Vec<Address> {
     let mut rng = ChaCha20Rng::from_seed(seed);
-    let mut winners = HashMap::with_capacity(num_winners as usize);
 
-    let mut set = std::collections::HashSet::new();
+    let mut set = HashSet::new();
 
     // Construct the cumulative weights (e.g; [1, 2, 3, 4] -> [1, 3, 6, 10])
     let mut cumulative_weights = Vec::with_capacity(votes.len());
-    let mut curr = votes[0].voting_power;
-    cumulative_weights.push(curr);
-    for v in votes.iter().skip(1) {
-        curr += v.voting_power;
+    let mut num_candidates = 0;
+    let mut curr = 0.0;
+    for v in votes.iter() {
+        let next = curr + v.voting_power;
+        if next > curr {
+            num_candidates += 1;
+        }
+        curr = next;
         cumulative_weights.push(curr);
     }
 
+    let num_winners = std::cmp::min(num_winners as usize, num_candidates);
+    let mut winners = Vec::with_capacity(num_winners);
+    if num_winners == 0 {
+        return winners;
+    }
+
     // TODO: we could optimize by sorting by votes and then poping the last element (which has the highest
     // probability of getting picked). For now, we don't optimize.
     let range = 0.0..*cumulative_weights.last().unwrap();
@@ -191,79 +652,114 @@ fn draw_winners(
 
         // Add winner to the set
         set.insert(winner);
-        // Add winner to the winners map
-        winners.insert(winner, prize);
+        // Add winner to the winners
+        winners.push(winner);
     }
     winners
 }
 
-// This function tries to map a timestamp to a randao reveal.
-// A randao reveal is a source of randomness provided by the beacon chain. Each epoch has a randao reveal.
-// This function tries to find the epoch that contains the timestamp and returns the randao reveal of that epoch.
-// It does so by following these steps:
-// Step 1: Find the closest slot corresponding to the timestamp (round up to nearest multiple of 12, because slots are 12 seconds long).
-// Step 2: Query the SLOT_URL to get the corresponding slot, and extract its epoch.
-// Step 3: Query the EPOCH_URL to get the corresponding epoch.
-// Step 4: Ensure the epoch is finalized, and return its randao reveal.
-async fn randao_from_timestamp(timestamp: u64) -> Result<String, ServerError> {
-    let client = reqwest::Client::new();
-
-    // Step 1
-    let elapsed = timestamp - FIRST_MERGED_SLOT_TIMESTAMP;
-    let rounded_elapsed = elapsed + (12 - elapsed % 12);
-    let elapsed_slots = rounded_elapsed / 12;
-    let nearest_slot = FIRST_MERGED_SLOT + elapsed_slots;
-
-    // Step 2
-    let slot_url = format!(
-        "{}{}?apikey={}",
-        SLOT_URL.as_str(),
-        nearest_slot,
-        BEACONCHAIN_API_KEY.as_str()
-    );
-    let slot: Value = client.get(&slot_url).send().await?.json().await?;
-    tracing::info!(?slot);
-    let epoch = slot["data"]["epoch"]
-        .as_u64()
-        .ok_or("failed to parse epoch")?;
-    tracing::info!(?epoch);
-
-    // Step 3
-    let epoch_url = format!(
-        "{}{}?apikey={}",
-        EPOCH_URL.as_str(),
-        epoch,
-        BEACONCHAIN_API_KEY.as_str()
-    );
-    let epoch_details: Value = client.get(&epoch_url).send().await?.json().await?;
-
-    // Step 4
-    let finalized = epoch_details["data"]["finalized"]
-        .as_bool()
-        .ok_or("finalized is not a boolean")?;
-    if !finalized {
-        return Err("epoch is not finalized".into());
-    }
-
-    let randao_reveal = slot["data"]["randaoreveal"]
-        .as_str()
-        .ok_or("randao_reveal is not a string")?
-        .to_string();
-
-    tracing::info!(?randao_reveal);
-    Ok(randao_reveal)
-}
-
-// Create a 32bytes seed with the sha256 hash of the randao reveal corresponding to
-// the next nearest epoch of the given timestamp.
-async fn get_randao_reveal(timestamp: u64) -> Result<[u8; 32], ServerError> {
-    // Step 1: Get the randao reveal from the chain
-    let randao = randao_from_timestamp(timestamp).await?;
-    let bytes = hex::decode(&randao[2..]).unwrap();
-
-    // Step 2: Hash the byte array
+// Draws without replacement: the weight of every winner is removed from a Fenwick tree (binary indexed tree) of the
+// weights, so that each draw is a `O(log n)` search among the remaining candidates and never needs to be repeated.
+// Stops early if the remaining candidates all have a weight of 0, or a weight too small to register next to the weights
+// that were drawn (e.g. `1e-9` next to `1e8`).
+fn fenwick_sampling(votes: &[Vote], seed: [u8; 32], num_winners: u32) -> Vec<Address> {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let mut winners = Vec::with_capacity(num_winners as usize);
+
+    let mut weights: Vec<f64> = votes.iter().map(|v| v.voting_power.max(0.0)).collect();
+    let mut tree = FenwickTree::new(&weights);
+    let mut remaining_candidates = weights.iter().filter(|w| **w > 0.0).count();
+
+    while winners.len() < num_winners as usize && remaining_candidates > 0 {
+        // The remaining weight is read from the tree rather than tracked separately, so that it always matches what
+        // the search sees
+        let remaining_weight = tree.total();
+        if remaining_weight <= 0.0 {
+            break;
+        }
+
+        // Rounding errors can still make the search land on a candidate that was already drawn (whose weight is now 0)
+        // or past the last candidate: just draw again.
+        let rnd: f64 = rng.gen_range(0.0..remaining_weight);
+        let idx = tree.search(rnd);
+        if idx >= weights.len() || weights[idx] == 0.0 {
+            continue;
+        }
+
+        winners.push(votes[idx].voter);
+        tree.add(idx, -weights[idx]);
+        remaining_candidates -= 1;
+        weights[idx] = 0.0;
+    }
+    winners
+}
+
+// Prefix sums of the weights that can be updated and searched in `O(log n)`.
+struct FenwickTree {
+    tree: Vec<f64>, // 1-indexed, `tree[i]` is the sum of the weights in `(i - lowbit(i), i]`
+}
+
+impl FenwickTree {
+    fn new(weights: &[f64]) -> Self {
+        let mut tree = vec![0.0; weights.len() + 1];
+        for (i, weight) in weights.iter().enumerate() {
+            let i = i + 1;
+            tree[i] += weight;
+            let parent = i + (i & i.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Self { tree }
+    }
+
+    fn add(&mut self, idx: usize, delta: f64) {
+        let mut i = idx + 1;
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // The sum of all the weights
+    fn total(&self) -> f64 {
+        let mut i = self.tree.len() - 1;
+        let mut sum = 0.0;
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+
+    // Returns the index of the first weight whose prefix sum is greater than `target`, or the number of weights if
+    // there is none.
+    fn search(&self, mut target: f64) -> usize {
+        let len = self.tree.len() - 1;
+        let mut pos = 0;
+        let mut step = if len == 0 { 0 } else { 1 << len.ilog2() };
+        while step > 0 {
+            if pos + step <= len && self.tree[pos + step] <= target {
+                pos += step;
+                target -= self.tree[pos];
+            }
+            step >>= 1;
+        }
+        pos
+    }
+}
+
+// Create a 32bytes seed with the sha256 hash of a randao reveal, followed by the salt if any.
+fn seed_from_randao(randao_reveal: &str, salt: Option<&str>) -> Result<[u8; 32], ServerError> {
+    // Step 1: Decode the randao reveal
+    let bytes = hex::decode(randao_reveal.trim_start_matches("0x"))?;
+
+    // Step 2: Hash the byte array, and the salt
     let mut hasher = Sha256::new();
     hasher.update(&bytes);
+    if let Some(salt) = salt {
+        hasher.update(salt.as_bytes());
+    }
 
     // Step 3: Convert the hash bytes to a fixed-size array for the seed
     let seed = hasher.finalize().into();
@@ -274,11 +770,12 @@ async fn get_randao_reveal(timestamp: u64) -> Result<[u8; 32], ServerError> {
 #[cfg(test)]
 mod test_draw_winners {
     use super::draw_winners;
-    use super::Vote;
-    use super::U256;
+    use super::{Sampling, Vote};
+    use ethers::types::Address;
     use rand::Rng;
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
+    use std::collections::HashSet;
 
     #[test]
     fn test_randomness() {
@@ -291,15 +788,14 @@ mod test_draw_winners {
             ..Default::default()
         };
         let votes = vec![vote1.clone(), vote2.clone()];
-        let prize = U256::from(10);
 
         let mut rng = ChaCha8Rng::from_entropy();
         let mut num = 0;
 
         // Draw 10000 times, expect voter 2 to get picked about 100 times.
         for _ in 0..10000 {
-            let winners = draw_winners(votes.clone(), rng.gen(), 1, prize);
-            if winners.get(&vote2.voter).is_some() {
+            let winners = draw_winners(&votes, rng.gen(), 1, Sampling::Rejection);
+            if winners.contains(&vote2.voter) {
                 num += 1;
             }
         }
@@ -324,32 +820,138 @@ mod test_draw_winners {
             ..Default::default()
         };
         let votes = vec![vote1.clone(), vote2.clone(), vote3.clone()];
-        let prize = U256::from(10);
 
         let mut rng = ChaCha8Rng::from_entropy();
 
-        let winners = draw_winners(votes, rng.gen(), 2, prize);
+        let winners = draw_winners(&votes, rng.gen(), 2, Sampling::Rejection);
         assert_eq!(winners.len(), 2);
     }
 
     #[test]
     #[cfg(feature = "expensive_tests")]
     fn test_speed() {
-        let votes = (0..1000000)
+        let votes: Vec<Vote> = (0..1000000)
             .enumerate()
             .map(|(i, _)| Vote {
                 voting_power: i as f64,
                 ..Default::default()
             })
             .collect();
-        let prize = U256::from(10);
 
         let mut rng = ChaCha8Rng::from_entropy();
 
+        for sampling in [Sampling::Rejection, Sampling::Fenwick] {
+            let start = std::time::Instant::now();
+            let _ = draw_winners(&votes, rng.gen(), 1000, sampling);
+            let finish = std::time::Instant::now();
+            println!("{:?}: {:?}", sampling, finish - start);
+        }
+
+        // Drawing almost every voter is where rejection sampling degrades the most
         let start = std::time::Instant::now();
-        let _ = draw_winners(votes, rng.gen(), 1000, prize);
+        let winners = draw_winners(&votes, rng.gen(), 999_000, Sampling::Fenwick);
         let finish = std::time::Instant::now();
-        println!("Time: {:?}", finish - start);
+        assert_eq!(winners.len(), 999_000);
+        println!("Fenwick, 999000 winners: {:?}", finish - start);
+    }
+
+    #[test]
+    fn test_fenwick_randomness() {
+        let votes = [60.0, 30.0, 10.0]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut first = [0; 3];
+        for _ in 0..10000 {
+            let winners = draw_winners(&votes, rng.gen(), 1, Sampling::Fenwick);
+            let idx = votes.iter().position(|v| v.voter == winners[0]).unwrap();
+            first[idx] += 1;
+        }
+
+        // Expect about 6000, 3000 and 1000, with a margin of error
+        assert!((5700..=6300).contains(&first[0]));
+        assert!((2700..=3300).contains(&first[1]));
+        assert!((800..=1200).contains(&first[2]));
+    }
+
+    #[test]
+    fn test_fenwick_without_replacement() {
+        let votes = (0..100)
+            .map(|i| Vote {
+                voter: Address::random(),
+                voting_power: if i % 10 == 0 { 0.0 } else { i as f64 },
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let seed = ChaCha8Rng::seed_from_u64(1).gen();
+
+        // Every candidate with a positive weight ends up drawn exactly once
+        let winners = draw_winners(&votes, seed, 100, Sampling::Fenwick);
+        assert_eq!(winners.len(), 90);
+        let unique: HashSet<_> = winners.iter().collect();
+        assert_eq!(unique.len(), 90);
+        assert!(votes
+            .iter()
+            .filter(|v| v.voting_power == 0.0)
+            .all(|v| !unique.contains(&v.voter)));
+
+        // Deterministic for a given seed
+        assert_eq!(draw_winners(&votes, seed, 100, Sampling::Fenwick), winners);
+    }
+
+    #[test]
+    fn test_rejection_not_enough_candidates() {
+        let votes = [10.0, 0.0, 0.0]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        // Only one candidate can ever be drawn
+        let winners = draw_winners(&votes, [1u8; 32], 3, Sampling::Rejection);
+        assert_eq!(winners, [votes[0].voter]);
+
+        // `1e8 + 1e-9 == 1e8`, so the small voter can't be drawn either
+        let votes = [1e8, 1e-9]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let winners = draw_winners(&votes, [1u8; 32], 2, Sampling::Rejection);
+        assert_eq!(winners, [votes[0].voter]);
+
+        assert!(draw_winners(&[], [1u8; 32], 2, Sampling::Rejection).is_empty());
+    }
+
+    #[test]
+    fn test_fenwick_dust() {
+        // `1e8 + 1e-9 == 1e8`, so nothing is left to draw from once the big voter has won
+        let votes = [1e8, 1e-9]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..100 {
+            let winners = draw_winners(&votes, rng.gen(), 2, Sampling::Fenwick);
+            assert!(winners.contains(&votes[0].voter));
+            let unique: HashSet<_> = winners.iter().collect();
+            assert_eq!(unique.len(), winners.len());
+        }
     }
 }
 
@@ -564,3 +1166,436 @@ mod test_adjust_vote_weights {
         assert_eq!(votes[6].voting_power, 50.0);
     }
 }
+
+#[cfg(test)]
+mod test_lottery_proof {
+    use super::{
+        canonical_order, draw, LotteryProof, LotteryRules, LotteryWeighting, Randao, Sampling,
+        SeedParams, Vote,
+    };
+    use crate::strategy::CURRENT_VERSION;
+    use ethers::types::Address;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn votes() -> Vec<Vote> {
+        [500.0, 200.0, 120.0, 100.0, 80.0]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    const PROPOSAL_END: u64 = 1_700_000_000;
+
+    fn seed_of(version: &str) -> SeedParams {
+        SeedParams {
+            proposal_id: "0x1234".to_string(),
+            proposal_end: PROPOSAL_END,
+            boost_id: "1".to_string(),
+            chain_id: "1".to_string(),
+            version: version.to_string(),
+            randomness: LotteryRules::of_version(version).unwrap().randomness,
+        }
+    }
+
+    fn seed() -> SeedParams {
+        seed_of(CURRENT_VERSION)
+    }
+
+    // The reveal of the slot that seeds the draw
+    fn randao(seed: &SeedParams) -> Randao {
+        let slot = seed.slot();
+        Randao {
+            slot,
+            epoch: slot / 32,
+            reveal: format!("0x{}", hex::encode([7u8; 96])),
+            missed_slots: vec![],
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let votes = votes();
+        let score = votes.iter().map(|v| v.voting_power).sum();
+        let proof = draw(
+            &votes,
+            randao(&seed()),
+            &seed(),
+            2,
+            Some(3000),
+            Sampling::Fenwick,
+            LotteryWeighting::Vp,
+            18,
+            score,
+        )
+        .unwrap();
+        assert_eq!(proof.winners.len(), 2);
+        assert_eq!(proof.candidates.len(), votes.len());
+        assert_eq!(proof.slot, seed().slot());
+        assert_eq!(proof.delay_epochs, 2);
+        assert_eq!(proof.proposal_end, PROPOSAL_END);
+
+        // The published proof is enough to reproduce the draw
+        let json = serde_json::to_string(&proof).unwrap();
+        let published: LotteryProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(published.replay().unwrap(), proof.winners);
+        assert!(published.verify().unwrap());
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        // Lots of ties, as is common when voting power comes from a whitelist or a single token
+        let mut votes: Vec<Vote> = (0..50)
+            .map(|i| Vote {
+                voter: Address::random(),
+                voting_power: (i % 3) as f64 + 1.0,
+                ..Default::default()
+            })
+            .collect();
+        canonical_order(&mut votes);
+        let reference = draw(
+            &votes,
+            randao(&seed()),
+            &seed(),
+            10,
+            None,
+            Sampling::Fenwick,
+            LotteryWeighting::Vp,
+            18,
+            100.0,
+        )
+        .unwrap();
+
+        // Candidates are sorted by voting power, then by address
+        assert!(reference.candidates.windows(2).all(|w| {
+            w[0].weight > w[1].weight || (w[0].weight == w[1].weight && w[0].voter < w[1].voter)
+        }));
+
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        for _ in 0..20 {
+            let mut shuffled = votes.clone();
+            shuffled.shuffle(&mut rng);
+            canonical_order(&mut shuffled);
+            let proof = draw(
+                &shuffled,
+                randao(&seed()),
+                &seed(),
+                10,
+                None,
+                Sampling::Fenwick,
+                LotteryWeighting::Vp,
+                18,
+                100.0,
+            )
+            .unwrap();
+            assert_eq!(proof.winners, reference.winners);
+            assert_eq!(proof.candidates_hash, reference.candidates_hash);
+        }
+
+        // Without the canonical order, the candidates are drawn in the order they were given
+        let mut shuffled = votes.clone();
+        shuffled.shuffle(&mut rng);
+        let proof = draw(
+            &shuffled,
+            randao(&seed()),
+            &seed(),
+            10,
+            None,
+            Sampling::Fenwick,
+            LotteryWeighting::Vp,
+            18,
+            100.0,
+        )
+        .unwrap();
+        assert!(proof
+            .candidates
+            .iter()
+            .zip(&shuffled)
+            .all(|(c, v)| c.voter == v.voter));
+        // which only replays under a version that doesn't sort them
+        assert!(proof.replay().is_err());
+        let mut legacy = proof.clone();
+        legacy.version = "0.0.3".to_string();
+        assert_eq!(legacy.replay().unwrap(), proof.winners);
+    }
+
+    #[test]
+    fn test_weighting() {
+        let votes = votes();
+        let score = votes.iter().map(|v| v.voting_power).sum();
+
+        // Every weighting is recorded in the proof, and replays to the same winners
+        for weighting in [
+            LotteryWeighting::Uniform,
+            LotteryWeighting::Vp,
+            LotteryWeighting::SqrtVp,
+        ] {
+            let proof = draw(
+                &votes,
+                randao(&seed()),
+                &seed(),
+                2,
+                Some(3000),
+                Sampling::Fenwick,
+                weighting,
+                18,
+                score,
+            )
+            .unwrap();
+            assert_eq!(proof.weighting, weighting);
+            assert_eq!(proof.replay().unwrap(), proof.winners);
+
+            // Replaying with another weighting does not match the committed candidates
+            let mut tampered = proof.clone();
+            tampered.weighting = match weighting {
+                LotteryWeighting::Uniform => LotteryWeighting::SqrtVp,
+                _ => LotteryWeighting::Uniform,
+            };
+            assert!(tampered.replay().is_err());
+        }
+
+        assert_eq!(LotteryWeighting::Uniform.weight(500.0), 1.0);
+        assert_eq!(LotteryWeighting::SqrtVp.weight(400.0), 20.0);
+        assert_eq!(
+            LotteryWeighting::try_from("sqrt-vp"),
+            Ok(LotteryWeighting::SqrtVp)
+        );
+        assert!(LotteryWeighting::try_from("quadratic").is_err());
+    }
+
+    #[test]
+    fn test_uniform_odds() {
+        // A whale does not get better odds than anyone else
+        let votes: Vec<Vote> = [1_000_000.0, 1.0]
+            .into_iter()
+            .map(|voting_power| Vote {
+                voter: Address::random(),
+                voting_power,
+                ..Default::default()
+            })
+            .collect();
+        let mut wins = 0;
+        for i in 0..2000u32 {
+            let randao = Randao {
+                reveal: format!("0x{}", hex::encode(i.to_be_bytes())),
+                ..randao(&seed())
+            };
+            let proof = draw(
+                &votes,
+                randao,
+                &seed(),
+                1,
+                None,
+                Sampling::Fenwick,
+                LotteryWeighting::Uniform,
+                18,
+                1_000_001.0,
+            )
+            .unwrap();
+            if proof.winners[0] == votes[1].voter {
+                wins += 1;
+            }
+        }
+        assert!((900..=1100).contains(&wins));
+    }
+
+    #[test]
+    fn test_salt() {
+        // Boosts of the same proposal get independent draws from the same randao reveal
+        let votes = votes();
+        let draw_with = |boost_id: &str, version: &str| {
+            let seed = SeedParams {
+                boost_id: boost_id.to_string(),
+                ..seed_of(version)
+            };
+            draw(
+                &votes,
+                randao(&seed),
+                &seed,
+                1,
+                None,
+                Sampling::Fenwick,
+                LotteryWeighting::Vp,
+                18,
+                0.0,
+            )
+            .unwrap()
+        };
+        let shared = draw_with("1", "0.0.2");
+        let first = draw_with("1", "0.0.4");
+        let second = draw_with("2", "0.0.4");
+        assert_eq!(first.salt.as_deref(), Some("0x1234:1:1"));
+        assert_ne!(first.seed, shared.seed);
+        assert_ne!(first.seed, second.seed);
+        assert_eq!(first.replay().unwrap(), first.winners);
+
+        // The salt is part of the proof, and must belong to the boost
+        let mut tampered = first.clone();
+        tampered.salt = second.salt.clone();
+        assert!(tampered.replay().is_err());
+        let mut tampered = second.clone();
+        tampered.boost_id = "1".to_string();
+        assert!(tampered.replay().is_err());
+
+        // Whether the seed is salted, and how long after the end of the proposal, depends on the version alone
+        let mut tampered = first.clone();
+        tampered.salt = None;
+        assert!(tampered.replay().is_err());
+        let mut tampered = shared.clone();
+        tampered.salt = first.salt.clone();
+        assert!(tampered.replay().is_err());
+        let mut tampered = first.clone();
+        tampered.version = "0.0.2".to_string();
+        assert!(tampered.replay().is_err());
+        let mut tampered = first.clone();
+        tampered.delay_epochs = 0;
+        tampered.slot = shared.slot;
+        assert!(tampered.replay().is_err());
+
+        // Proofs without a salt still deserialize
+        let json = serde_json::to_string(&shared).unwrap();
+        assert!(!json.contains("salt"));
+        let published: LotteryProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(published.replay().unwrap(), shared.winners);
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let votes = votes();
+        let seed = seed_of("0.0.1");
+        let proof = draw(
+            &votes,
+            randao(&seed),
+            &seed,
+            2,
+            None,
+            Sampling::Rejection,
+            LotteryWeighting::Vp,
+            18,
+            1000.0,
+        )
+        .unwrap();
+
+        let mut tampered = proof.clone();
+        tampered.candidates[4].weight = 10_000.0;
+        assert!(tampered.replay().is_err());
+
+        let mut tampered = proof.clone();
+        tampered.randao_reveal = format!("0x{}", hex::encode([8u8; 96]));
+        assert!(tampered.replay().is_err());
+
+        // The slot must follow the end of the proposal by the delay, or be the first proposed one after it
+        let mut missed = proof.clone();
+        missed.slot += 1;
+        assert!(missed.replay().is_err());
+        missed.missed_slots = vec![proof.slot];
+        assert_eq!(missed.replay().unwrap(), proof.winners);
+        missed.missed_slots = vec![proof.slot - 1];
+        assert!(missed.replay().is_err());
+        let mut tampered = proof.clone();
+        tampered.delay_epochs = 2;
+        assert!(tampered.replay().is_err());
+        let mut tampered = proof.clone();
+        tampered.proposal_end += 3600;
+        assert!(tampered.replay().is_err());
+
+        // The sampling is set by the version
+        let mut tampered = proof.clone();
+        tampered.sampling = Sampling::Fenwick;
+        assert!(tampered.replay().is_err());
+        let mut tampered = proof.clone();
+        tampered.version = "0.0.2".to_string();
+        assert!(tampered.replay().is_err());
+        tampered.version = "0.0.5".to_string();
+        assert!(tampered.replay().is_err());
+
+        // Publishing other winners than the drawn ones
+        let mut tampered = proof.clone();
+        tampered.winners[0] = Address::random();
+        assert!(!tampered.verify().unwrap());
+
+        let mut empty = proof.clone();
+        empty.candidates.clear();
+        assert!(empty.replay().is_err());
+    }
+}
+
+#[cfg(test)]
+mod test_prize_tiers {
+    use super::{PrizeTier, PrizeTiers};
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn test_parse() {
+        let tiers = PrizeTiers::parse(Some(&[
+            "1:5000".to_string(),
+            "5:3000".to_string(),
+            "50:2000".to_string(),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            tiers.tiers[1],
+            PrizeTier {
+                num_winners: 5,
+                share: 3000
+            }
+        );
+        assert_eq!(tiers.num_winners(), 56);
+
+        assert_eq!(PrizeTiers::parse(None), Ok(None));
+        assert!(PrizeTiers::parse(Some(&["0:5000".to_string()])).is_err());
+        assert!(PrizeTiers::parse(Some(&["1-5000".to_string()])).is_err());
+        assert!(PrizeTiers::parse(Some(&["1:6000".to_string(), "2:5000".to_string()])).is_err());
+    }
+
+    #[test]
+    fn test_prizes() {
+        let tiers = PrizeTiers {
+            tiers: vec![
+                PrizeTier {
+                    num_winners: 1,
+                    share: 5000,
+                },
+                PrizeTier {
+                    num_winners: 3,
+                    share: 4000,
+                },
+            ],
+        };
+        let prizes = tiers.prizes(U256::from(1000));
+        assert_eq!(prizes, [500, 133, 133, 133].map(U256::from).to_vec());
+    }
+
+    #[test]
+    fn test_distribution_remainder() {
+        let tiers = PrizeTiers {
+            tiers: vec![
+                PrizeTier {
+                    num_winners: 1,
+                    share: 5000,
+                },
+                PrizeTier {
+                    num_winners: 3,
+                    share: 5000,
+                },
+            ],
+        };
+        // The dust of the second tier stays in the pool, rather than going to the first winner drawn
+        let winners: Vec<Address> = (0..4).map(|_| Address::random()).collect();
+        let distribution = tiers.distribution(&winners, U256::from(1000)).unwrap();
+        assert_eq!(distribution.rewards[&winners[0]], U256::from(500));
+        assert_eq!(distribution.rewards[&winners[3]], U256::from(166));
+        assert_eq!(distribution.leftover, U256::from(2));
+
+        // So do the prizes nobody won
+        let distribution = tiers.distribution(&winners[..2], U256::from(1000)).unwrap();
+        assert_eq!(distribution.total, U256::from(666));
+        assert_eq!(distribution.leftover, U256::from(334));
+    }
+}
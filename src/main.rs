@@ -1,5 +1,6 @@
 use axum::routing::{get, post};
 use axum::{Extension, Router};
+use boost_guard::lottery::LotteryProof;
 use boost_guard::routes::{handle_create_vouchers, handle_get_rewards, handle_health, handle_root};
 use mysql_async::Pool;
 use std::env;
@@ -12,6 +13,11 @@ use dotenv::dotenv;
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("verify-lottery") {
+        std::process::exit(verify_lottery(args.get(2).map(String::as_str)));
+    }
+
     dotenv().ok();
 
     // construct a subscriber that prints formatted traces to stdout
@@ -29,6 +35,45 @@ async fn main() {
     axum::serve(listener, app()).await.unwrap();
 }
 
+// Reruns the draw of a lottery from a proof, as returned by `/get-lottery-proof`, without any database or network
+// access. Returns the exit code: 0 if the published winners match the draw, 1 if they don't, 2 if the proof is unusable
+// (unreadable, or its seed or candidates don't match what it commits to).
+fn verify_lottery(path: Option<&str>) -> i32 {
+    let Some(path) = path else {
+        eprintln!("usage: boost-guard verify-lottery <proof.json>");
+        return 2;
+    };
+    let proof: LotteryProof = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+    {
+        Ok(proof) => proof,
+        Err(error) => {
+            eprintln!("failed to read proof {}: {}", path, error);
+            return 2;
+        }
+    };
+
+    match proof.replay() {
+        Ok(winners) if winners == proof.winners => {
+            println!("OK: the published winners match the draw");
+            0
+        }
+        Ok(winners) => {
+            println!("MISMATCH: the published winners do not match the draw");
+            println!("drawn winners:");
+            winners.iter().for_each(|w| println!("  {:?}", w));
+            println!("published winners:");
+            proof.winners.iter().for_each(|w| println!("  {:?}", w));
+            1
+        }
+        Err(error) => {
+            eprintln!("invalid proof: {:?}", error);
+            2
+        }
+    }
+}
+
 fn app() -> Router {
     dotenv().ok();
 
@@ -53,12 +98,21 @@ fn app() -> Router {
             "/get-lottery-winners",
             post(boost_guard::routes::handle_get_lottery_winners),
         )
+        .route(
+            "/get-lottery-proof",
+            post(boost_guard::routes::handle_get_lottery_proof),
+        )
+        .route(
+            "/get-boost-diagnostics",
+            post(boost_guard::routes::handle_get_boost_diagnostics),
+        )
         .route("/health", get(handle_health))
         .route("/", get(handle_root))
         .layer(Extension(state))
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_unwrap)]
 mod tests {
     use axum::body::Body;
     use axum::http;
@@ -78,7 +132,7 @@ mod tests {
     async fn test_create_vouchers() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: PROPOSAL_ID.to_string(),
+            proposal_id: Some(PROPOSAL_ID.to_string()),
             voter_address: WINNER.to_string(),
             boosts: vec![(BOOST_ID.to_string(), CHAIN_ID.to_string())],
         };
@@ -114,7 +168,7 @@ mod tests {
     async fn test_get_rewards() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: PROPOSAL_ID.to_string(),
+            proposal_id: Some(PROPOSAL_ID.to_string()),
             voter_address: WINNER.to_string(),
             boosts: vec![(BOOST_ID.to_string(), CHAIN_ID.to_string())],
         };
@@ -152,6 +206,8 @@ mod tests {
             proposal_id: PROPOSAL_ID.to_string(),
             boost_id: BOOST_ID.to_string(),
             chain_id: CHAIN_ID.to_string(),
+            offset: None,
+            limit: None,
         };
 
         let response = app
@@ -174,8 +230,10 @@ mod tests {
         } else {
             let result = response.unwrap();
             assert_eq!(result.winners.len(), 1);
-            assert_eq!(result.winners[0], WINNER);
-            assert_eq!(result.prize, "10000000000000000");
+            assert_eq!(result.num_winners, 1);
+            assert_eq!(result.winners[0].address, WINNER);
+            assert_eq!(result.winners[0].rank, 1);
+            assert_eq!(result.winners[0].prize, "10000000000000000");
         }
     }
 
@@ -222,8 +280,9 @@ mod tests {
     async fn test_get_rewards_ranked_choice() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: "0x930d5fb011f84d16df26c362d820323f0dab111c3b0b91d75151fe12c5ff07fb"
-                .to_string(),
+            proposal_id: Some(
+                "0x930d5fb011f84d16df26c362d820323f0dab111c3b0b91d75151fe12c5ff07fb".to_string(),
+            ),
             voter_address: "0x5ef29cf961cf3fc02551b9bdadaa4418c446c5dd".to_string(),
             boosts: vec![
                 ("42".to_string(), "11155111".to_string()),
@@ -261,8 +320,9 @@ mod tests {
     async fn test_get_rewards_shutter() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: "0xdde52de1d892ccc671dcca55504803f87a2297089fd728ef2076af4c1b96ac1c"
-                .to_string(),
+            proposal_id: Some(
+                "0xdde52de1d892ccc671dcca55504803f87a2297089fd728ef2076af4c1b96ac1c".to_string(),
+            ),
             voter_address: "0x5ef29cf961cf3fc02551b9bdadaa4418c446c5dd".to_string(),
             boosts: vec![
                 ("44".to_string(), "11155111".to_string()),
@@ -300,8 +360,9 @@ mod tests {
     async fn test_get_rewards_shutter_and_ranked_choice() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: "0xfcdb01284958142a481fb4d579aa056ed93c29a9f58fbefbfb0504b3c1c06e96"
-                .to_string(),
+            proposal_id: Some(
+                "0xfcdb01284958142a481fb4d579aa056ed93c29a9f58fbefbfb0504b3c1c06e96".to_string(),
+            ),
             voter_address: "0xc83A9e69012312513328992d454290be85e95101".to_string(),
             boosts: vec![
                 ("46".to_string(), "11155111".to_string()),
@@ -339,8 +400,9 @@ mod tests {
     async fn test_get_rewards_shutter_and_ranked_proportional() {
         let app = super::app();
         let query = QueryParams {
-            proposal_id: "0xe175412d46744bdb68e61c89492a5d3ebb55a487cf8fc4d35a0d671302babed3"
-                .to_string(),
+            proposal_id: Some(
+                "0xe175412d46744bdb68e61c89492a5d3ebb55a487cf8fc4d35a0d671302babed3".to_string(),
+            ),
             voter_address: "0xc83A9e69012312513328992d454290be85e95101".to_string(),
             boosts: vec![("49".to_string(), "11155111".to_string())],
         };
@@ -370,4 +432,81 @@ mod tests {
             assert_eq!(result[0].boost_id, "49");
         }
     }
+
+    #[test]
+    fn test_verify_lottery() {
+        use boost_guard::lottery::{draw, LotteryProof, LotteryWeighting, Sampling, SeedParams};
+        use boost_guard::randomness::{seed_slot, Randao, DELAYED_RANDOMNESS};
+        use boost_guard::routes::Vote;
+        use boost_guard::strategy::CURRENT_VERSION;
+        use ethers::types::Address;
+
+        let votes: Vec<Vote> = (1..=5)
+            .rev()
+            .map(|i| Vote {
+                voter: Address::from_low_u64_be(i),
+                voting_power: i as f64,
+                multiplier: 1.0,
+            })
+            .collect();
+        let seed = SeedParams {
+            proposal_id: PROPOSAL_ID.to_string(),
+            proposal_end: 1_700_000_000,
+            boost_id: BOOST_ID.to_string(),
+            chain_id: CHAIN_ID.to_string(),
+            version: CURRENT_VERSION.to_string(),
+            randomness: DELAYED_RANDOMNESS,
+        };
+        let slot = seed_slot(seed.proposal_end, 2);
+        let randao = Randao {
+            slot,
+            epoch: slot / 32,
+            reveal: format!("0x{}", hex::encode([7u8; 96])),
+            missed_slots: vec![],
+        };
+        let proof = draw(
+            &votes,
+            randao,
+            &seed,
+            2,
+            None,
+            Sampling::Fenwick,
+            LotteryWeighting::Vp,
+            18,
+            15.0,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("lottery-proof-{}.json", std::process::id()));
+        let verify = |proof: &LotteryProof| {
+            std::fs::write(&path, serde_json::to_string(proof).unwrap()).unwrap();
+            super::verify_lottery(path.to_str())
+        };
+
+        // The published winners match the draw
+        assert_eq!(verify(&proof), 0);
+
+        // The published winners don't match the draw
+        let mut tampered = proof.clone();
+        tampered.winners.reverse();
+        assert_eq!(verify(&tampered), 1);
+
+        // The proof doesn't match what it commits to
+        let mut tampered = proof.clone();
+        tampered.seed = format!("0x{}", hex::encode([0u8; 32]));
+        assert_eq!(verify(&tampered), 2);
+        let mut tampered = proof.clone();
+        tampered.candidates[0].weight += 1.0;
+        assert_eq!(verify(&tampered), 2);
+        let mut tampered = proof.clone();
+        tampered.boost_id = "4".to_string();
+        assert_eq!(verify(&tampered), 2);
+        let mut tampered = proof.clone();
+        tampered.version = "0.0.2".to_string();
+        assert_eq!(verify(&tampered), 2);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(super::verify_lottery(path.to_str()), 2);
+        assert_eq!(super::verify_lottery(None), 2);
+    }
 }
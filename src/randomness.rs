@@ -0,0 +1,409 @@
+use crate::ServerError;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+
+const FIRST_MERGED_SLOT: u64 = 4700013;
+const FIRST_MERGED_SLOT_TIMESTAMP: u64 = 1663224179;
+const SLOTS_PER_EPOCH: u64 = 32;
+// How many consecutive missed slots to skip before giving up, a whole epoch without any block would be exceptional
+const MAX_MISSED_SLOTS: u64 = 32;
+
+// The randao reveal of a slot, used to seed the lottery
+#[derive(Debug, Clone)]
+pub struct Randao {
+    pub slot: u64,
+    pub epoch: u64,
+    pub reveal: String,
+    pub missed_slots: Vec<u64>, // The slots skipped before `slot` because they had no canonical block
+}
+
+// How the seed of a lottery is picked.
+// The proposer of the slot right after the end of the proposal could withhold their block to bias the draw, waiting
+// a few epochs leaves the outcome of the proposal public long before anyone knows which slots will seed the draw.
+// Set by the version of the strategy, see `LotteryRules`: versions 0.0.2 and below use the default, which has no delay
+// and shares the seed across every boost of the proposal, so that their draws stay reproducible.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RandomnessParams {
+    pub delay_epochs: u64, // Epochs to wait after the end of the proposal
+    pub independent: bool, // The seed is mixed with the ids of the proposal and of the boost
+}
+
+// Used since version 0.0.3
+pub const DELAYED_RANDOMNESS: RandomnessParams = RandomnessParams {
+    delay_epochs: 2,
+    independent: true,
+};
+
+pub type RandaoFuture<'a> = Pin<Box<dyn Future<Output = Result<Randao, ServerError>> + Send + 'a>>;
+
+// Where the randomness of the lotteries comes from.
+// A randao reveal is a source of randomness provided by the beacon chain: every block reveals one. Sources map a slot
+// (see `seed_slot`) to the randao reveal of the first canonical block from it, and only return it once its epoch is
+// finalized, so that the seed of a draw can never change. Missed (or orphaned) slots are skipped, up to
+// `MAX_MISSED_SLOTS`, and the slot that was actually used is returned.
+pub trait RandomnessSource: Send + Sync {
+    fn randao(&self, slot: u64) -> RandaoFuture<'_>;
+}
+
+// The first slot that may seed the lottery of a proposal ending at `timestamp`
+pub fn seed_slot(timestamp: u64, delay_epochs: u64) -> u64 {
+    slot_from_timestamp(timestamp) + delay_epochs * SLOTS_PER_EPOCH
+}
+
+// Whether `slot` is the one that seeds the lottery of a proposal ending at `timestamp`: the first slot, or the first one
+// with a block after it. Every slot in between must be listed in `missed_slots`, so that anyone can check on the beacon
+// chain that none of them had a block, and nobody can pick among several slots.
+pub fn is_seed_slot(slot: u64, missed_slots: &[u64], timestamp: u64, delay_epochs: u64) -> bool {
+    let first = seed_slot(timestamp, delay_epochs);
+    slot >= first && slot - first < MAX_MISSED_SLOTS && missed_slots.iter().copied().eq(first..slot)
+}
+
+// Finds the slot following the timestamp (rounded up to the next multiple of 12, because slots are 12 seconds long).
+// There is no randao before the merge, so earlier timestamps map to the first slot after it.
+pub fn slot_from_timestamp(timestamp: u64) -> u64 {
+    let elapsed = timestamp.saturating_sub(FIRST_MERGED_SLOT_TIMESTAMP);
+    let rounded_elapsed = elapsed + (12 - elapsed % 12);
+    let elapsed_slots = rounded_elapsed / 12;
+    FIRST_MERGED_SLOT + elapsed_slots
+}
+
+// The beaconcha.in API, or any node exposing its `/api/v1/slot/` and `/api/v1/epoch/` endpoints.
+pub struct Beaconchain {
+    client: reqwest::Client,
+    slot_url: String,
+    epoch_url: String,
+    api_key: String,
+}
+
+impl Beaconchain {
+    pub fn new(slot_url: &str, epoch_url: &str, api_key: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            slot_url: slot_url.to_string(),
+            epoch_url: epoch_url.to_string(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    // Step 1: Query the slot, and extract its epoch.
+    // Step 2: Query the epoch, and ensure it is finalized.
+    // Step 3: If the slot was proposed, return its randao reveal. Otherwise, try the next slot.
+    async fn get_randao(&self, nearest_slot: u64) -> Result<Randao, ServerError> {
+        let mut finalized_epoch = None;
+        let mut missed_slots = Vec::new();
+        for slot_number in nearest_slot..nearest_slot + MAX_MISSED_SLOTS {
+            // Step 1
+            let slot_url = format!("{}{}?apikey={}", self.slot_url, slot_number, self.api_key);
+            let slot: Value = self.client.get(&slot_url).send().await?.json().await?;
+            tracing::info!(?slot);
+            let epoch = slot["data"]["epoch"]
+                .as_u64()
+                .ok_or("failed to parse epoch")?;
+            tracing::info!(?epoch);
+
+            // Step 2
+            if finalized_epoch != Some(epoch) {
+                let epoch_url = format!("{}{}?apikey={}", self.epoch_url, epoch, self.api_key);
+                let epoch_details: Value = self.client.get(&epoch_url).send().await?.json().await?;
+                let finalized = epoch_details["data"]["finalized"]
+                    .as_bool()
+                    .ok_or("finalized is not a boolean")?;
+                if !finalized {
+                    return Err("epoch is not finalized".into());
+                }
+                finalized_epoch = Some(epoch);
+            }
+
+            // Step 3
+            // The status of a slot is "1" once proposed, "2" if missed and "3" if orphaned
+            if slot["data"]["status"].as_str() != Some("1") {
+                tracing::warn!(
+                    slot = slot_number,
+                    "slot was not proposed, trying the next one"
+                );
+                missed_slots.push(slot_number);
+                continue;
+            }
+            let randao_reveal = slot["data"]["randaoreveal"]
+                .as_str()
+                .ok_or("randao_reveal is not a string")?
+                .to_string();
+            tracing::info!(?randao_reveal);
+            return Ok(Randao {
+                slot: slot_number,
+                epoch,
+                reveal: randao_reveal,
+                missed_slots,
+            });
+        }
+
+        Err(too_many_missed_slots(nearest_slot))
+    }
+}
+
+impl RandomnessSource for Beaconchain {
+    fn randao(&self, slot: u64) -> RandaoFuture<'_> {
+        Box::pin(self.get_randao(slot))
+    }
+}
+
+// Any beacon node, through the standard Ethereum beacon API.
+pub struct BeaconNode {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl BeaconNode {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // Step 1: Query the last finalized epoch.
+    // Step 2: Ensure the epoch of the slot is finalized.
+    // Step 3: Query the block of the slot, and return its randao reveal. If the slot has no canonical block, try the
+    // next slot.
+    async fn get_randao(&self, nearest_slot: u64) -> Result<Randao, ServerError> {
+        // Step 1
+        let checkpoints: Value = self
+            .client
+            .get(format!(
+                "{}/eth/v1/beacon/states/finalized/finality_checkpoints",
+                self.url
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let finalized_epoch: u64 = checkpoints["data"]["finalized"]["epoch"]
+            .as_str()
+            .and_then(|epoch| epoch.parse().ok())
+            .ok_or("failed to parse finalized epoch")?;
+
+        let mut missed_slots = Vec::new();
+        for slot in nearest_slot..nearest_slot + MAX_MISSED_SLOTS {
+            // Step 2
+            let epoch = slot / SLOTS_PER_EPOCH;
+            if epoch > finalized_epoch {
+                return Err("epoch is not finalized".into());
+            }
+
+            // Step 3
+            // Blocks are looked up by slot on the canonical chain, so orphaned blocks are never returned. The v1 endpoint
+            // is deprecated, and only serves the blocks from before the Altair fork
+            let response = self
+                .client
+                .get(format!("{}/eth/v2/beacon/blocks/{}", self.url, slot))
+                .send()
+                .await?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                tracing::warn!(slot, "slot has no block, trying the next one");
+                missed_slots.push(slot);
+                continue;
+            }
+            let block: Value = response.error_for_status()?.json().await?;
+            let randao_reveal = block["data"]["message"]["body"]["randao_reveal"]
+                .as_str()
+                .ok_or("randao_reveal is not a string")?
+                .to_string();
+            tracing::info!(slot, ?randao_reveal);
+            return Ok(Randao {
+                slot,
+                epoch,
+                reveal: randao_reveal,
+                missed_slots,
+            });
+        }
+
+        Err(too_many_missed_slots(nearest_slot))
+    }
+}
+
+impl RandomnessSource for BeaconNode {
+    fn randao(&self, slot: u64) -> RandaoFuture<'_> {
+        Box::pin(self.get_randao(slot))
+    }
+}
+
+fn too_many_missed_slots(slot: u64) -> ServerError {
+    ServerError::ErrorString(format!(
+        "no block was proposed in the {} slots from slot {}",
+        MAX_MISSED_SLOTS, slot
+    ))
+}
+
+#[cfg(test)]
+mod test_randomness {
+    use super::{
+        is_seed_slot, seed_slot, slot_from_timestamp, BeaconNode, Beaconchain, RandomnessSource,
+        MAX_MISSED_SLOTS,
+    };
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+
+    // Slot 4700013 + 100, in epoch 146878
+    const TIMESTAMP: u64 = 1663224179 + 1195;
+    const SLOT: u64 = 4700113;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    // A beacon node that finalized `finalized_epoch`, and only knows the blocks of `SLOT` and of the slots after
+    // `missed` slots
+    fn beacon_node(finalized_epoch: u64, missed: u64) -> Router {
+        Router::new()
+            .route(
+                "/eth/v1/beacon/states/finalized/finality_checkpoints",
+                get(move || async move {
+                    Json(json!({
+                        "data": {
+                            "previous_justified": { "epoch": (finalized_epoch + 1).to_string(), "root": "0x00" },
+                            "current_justified": { "epoch": (finalized_epoch + 2).to_string(), "root": "0x00" },
+                            "finalized": { "epoch": finalized_epoch.to_string(), "root": "0x00" }
+                        }
+                    }))
+                }),
+            )
+            .route(
+                "/eth/v2/beacon/blocks/:slot",
+                get(move |Path(slot): Path<u64>| async move {
+                    if slot != SLOT && slot < SLOT + 1 + missed {
+                        return Err(StatusCode::NOT_FOUND);
+                    }
+                    Ok(Json(json!({
+                        "version": "deneb",
+                        "finalized": true,
+                        "data": {
+                            "message": {
+                                "slot": slot.to_string(),
+                                "body": { "randao_reveal": format!("0x{:04x}", slot - SLOT) }
+                            }
+                        }
+                    })))
+                }),
+            )
+    }
+
+    #[test]
+    fn test_slot_from_timestamp() {
+        assert_eq!(slot_from_timestamp(TIMESTAMP), SLOT);
+        assert_eq!(slot_from_timestamp(TIMESTAMP - 5), SLOT);
+        assert_eq!(slot_from_timestamp(TIMESTAMP + 5), SLOT + 1);
+
+        // No underflow before the merge
+        assert_eq!(slot_from_timestamp(0), slot_from_timestamp(1663224179));
+
+        assert_eq!(seed_slot(TIMESTAMP, 0), SLOT);
+        assert_eq!(seed_slot(TIMESTAMP, 2), SLOT + 64);
+
+        assert!(is_seed_slot(SLOT + 64, &[], TIMESTAMP, 2));
+        assert!(!is_seed_slot(SLOT, &[], TIMESTAMP, 2));
+        // A later slot only if every slot before it was missed
+        assert!(!is_seed_slot(SLOT + 66, &[], TIMESTAMP, 2));
+        assert!(!is_seed_slot(SLOT + 66, &[SLOT + 64], TIMESTAMP, 2));
+        assert!(!is_seed_slot(
+            SLOT + 66,
+            &[SLOT + 65, SLOT + 64],
+            TIMESTAMP,
+            2
+        ));
+        assert!(is_seed_slot(
+            SLOT + 66,
+            &[SLOT + 64, SLOT + 65],
+            TIMESTAMP,
+            2
+        ));
+        let missed: Vec<u64> = (SLOT + 64..SLOT + 64 + MAX_MISSED_SLOTS).collect();
+        assert!(is_seed_slot(
+            SLOT + 64 + MAX_MISSED_SLOTS - 1,
+            &missed[..missed.len() - 1],
+            TIMESTAMP,
+            2
+        ));
+        assert!(!is_seed_slot(
+            SLOT + 64 + MAX_MISSED_SLOTS,
+            &missed,
+            TIMESTAMP,
+            2
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_beacon_node() {
+        let url = serve(beacon_node(SLOT / 32, 0)).await;
+        let randao = BeaconNode::new(&url).randao(SLOT).await.unwrap();
+        assert_eq!(randao.slot, SLOT);
+        assert_eq!(randao.epoch, SLOT / 32);
+        assert_eq!(randao.reveal, "0x0000");
+        assert!(randao.missed_slots.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_beacon_node_missed_slots() {
+        // The next proposed slot is used, and recorded
+        let url = serve(beacon_node(SLOT / 32 + 1, 3)).await;
+        let randao = BeaconNode::new(&url).randao(SLOT + 1).await.unwrap();
+        assert_eq!(randao.slot, SLOT + 4);
+        assert_eq!(randao.reveal, "0x0004");
+        assert_eq!(randao.missed_slots, [SLOT + 1, SLOT + 2, SLOT + 3]);
+
+        // The search is bounded
+        let url = serve(beacon_node(SLOT / 32 + 2, 40)).await;
+        let err = BeaconNode::new(&url).randao(SLOT + 1).await.unwrap_err();
+        assert_eq!(
+            err,
+            "no block was proposed in the 32 slots from slot 4700114".into()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_beacon_node_not_finalized() {
+        let url = serve(beacon_node(SLOT / 32 - 1, 0)).await;
+        let err = BeaconNode::new(&url).randao(SLOT).await.unwrap_err();
+        assert_eq!(err, "epoch is not finalized".into());
+    }
+
+    #[tokio::test]
+    async fn test_beaconchain() {
+        let app = Router::new()
+            .route(
+                "/api/v1/slot/:slot",
+                get(|Path(slot): Path<u64>| async move {
+                    // The first two slots were missed and orphaned
+                    let status = match slot - SLOT {
+                        0 => "2",
+                        1 => "3",
+                        _ => "1",
+                    };
+                    Json::<Value>(json!({
+                        "data": { "epoch": slot / 32, "status": status, "randaoreveal": "0xabcd" }
+                    }))
+                }),
+            )
+            .route(
+                "/api/v1/epoch/:epoch",
+                get(|| async { Json::<Value>(json!({ "data": { "finalized": true } })) }),
+            );
+        let url = serve(app).await;
+        let source = Beaconchain::new(
+            &format!("{}/api/v1/slot/", url),
+            &format!("{}/api/v1/epoch/", url),
+            "",
+        );
+        let randao = source.randao(SLOT).await.unwrap();
+        assert_eq!(randao.slot, SLOT + 2);
+        assert_eq!(randao.reveal, "0xabcd");
+        assert_eq!(randao.missed_slots, [SLOT, SLOT + 1]);
+    }
+}
@@ -0,0 +1,477 @@
+use crate::distribution::Distribution;
+use crate::routes::boost_query::BoostQueryBoostStrategy;
+use crate::routes::{
+    compute_distribution, parse_remainder_policy, BoostEligibility, BoostInfo, BoostParams,
+    DistributionType, ProposalInfo, Vote, VoteWithChoice,
+};
+use crate::strategy::{DistributionFuture, RewardStrategy};
+use crate::ServerError;
+use cached::proc_macro::cached;
+use cached::TimedSizedCache;
+use durations::WEEK;
+use ethers::types::Address;
+use mysql_async::params;
+use mysql_async::prelude::Queryable;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+// Parameters of a space boost: voters are rewarded for their participation in every proposal of the space that ran
+// within the window.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpaceParams {
+    pub space: String,
+    pub start: u64,
+    pub end: u64,
+    pub streak_bonus: Option<f64>, // Extra weight for every consecutive proposal voted on, after the first one
+}
+
+impl SpaceParams {
+    pub fn parse(
+        space: Option<&str>,
+        start: Option<&str>,
+        end: Option<&str>,
+        streak_bonus: Option<&str>,
+    ) -> Result<Self, &'static str> {
+        let space = space.ok_or("missing space")?.to_string();
+        if !is_valid_space_id(&space) {
+            return Err("invalid space");
+        }
+        let start = start
+            .ok_or("missing window start")?
+            .parse()
+            .map_err(|_| "failed to parse window start")?;
+        let end = end
+            .ok_or("missing window end")?
+            .parse()
+            .map_err(|_| "failed to parse window end")?;
+        if start >= end {
+            return Err("window start must be before window end");
+        }
+
+        let streak_bonus = match streak_bonus {
+            None => None,
+            Some(bonus) => {
+                let bonus: f64 = bonus.parse().map_err(|_| "failed to parse streak bonus")?;
+                if !bonus.is_finite() || bonus < 0.0 {
+                    return Err("invalid streak bonus");
+                }
+                Some(bonus)
+            }
+        };
+
+        Ok(Self {
+            space,
+            start,
+            end,
+            streak_bonus,
+        })
+    }
+}
+
+// Space ids are ENS names or other names registered on the hub, e.g. `snapshot.eth` or `s:snapshot.eth`
+fn is_valid_space_id(space: &str) -> bool {
+    !space.is_empty()
+        && space.len() <= MAX_SPACE_ID_LENGTH
+        && space
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'))
+}
+
+const MAX_SPACE_ID_LENGTH: usize = 256;
+
+// Boosts the participation in a space, over a time window.
+pub struct SpaceBoost;
+
+impl RewardStrategy for SpaceBoost {
+    fn parse(&self, strategy: BoostQueryBoostStrategy) -> Result<BoostParams, &'static str> {
+        let space = SpaceParams::parse(
+            strategy.space.as_deref(),
+            strategy.window_start.as_deref(),
+            strategy.window_end.as_deref(),
+            strategy.streak_bonus.as_deref(),
+        )?;
+        // These options only make sense for the votes of a single proposal
+        let eligibility_options = &strategy.eligibility;
+        if eligibility_options.min_voting_power.is_some()
+            || eligibility_options.min_reason_length.is_some()
+            || eligibility_options.excluded_roles.is_some()
+            || eligibility_options.denylist.is_some()
+            || eligibility_options.require_quorum.is_some()
+            || eligibility_options.min_votes.is_some()
+            || eligibility_options.skip_abstain.is_some()
+            || eligibility_options.tie_policy.is_some()
+        {
+            return Err("space boosts don't support eligibility options");
+        }
+        let distribution_options = &strategy.distribution;
+        if distribution_options.early_voter_multipliers.is_some()
+            || distribution_options.delegator_passthrough.is_some()
+            || distribution_options.prize_tiers.is_some()
+            || distribution_options.weighting.is_some()
+        {
+            return Err("space boosts don't support distribution options");
+        }
+
+        let remainder_policy =
+            parse_remainder_policy(strategy.distribution.remainder_policy.as_deref())?;
+
+        let eligibility = BoostEligibility::try_from(strategy.eligibility)?;
+        if !matches!(eligibility, BoostEligibility::Incentive) {
+            return Err("space boosts only support incentives");
+        }
+        // Voters are rewarded proportionally to their participation, so the distribution has to be weighted
+        let distribution = DistributionType::try_from(strategy.distribution)?;
+        if !matches!(distribution, DistributionType::Weighted(_)) {
+            return Err("space boosts only support weighted distributions");
+        }
+
+        Ok(BoostParams {
+            version: strategy.version,
+            proposal: strategy.proposal,
+            eligibility,
+            distribution,
+            remainder_policy,
+            space: Some(space),
+            ..Default::default()
+        })
+    }
+
+    // Space boosts reward the participation across proposals rather than a single vote: every voter in the
+    // distribution is eligible
+    fn eligible(
+        &self,
+        _vote: Option<(&ProposalInfo, &VoteWithChoice)>,
+        _boost_info: &BoostInfo,
+    ) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    fn distribute<'a>(
+        &'a self,
+        pool: &'a mysql_async::Pool,
+        boost_info: &'a BoostInfo,
+        _proposal_info: Option<&'a ProposalInfo>,
+    ) -> DistributionFuture<'a> {
+        Box::pin(cached_space_distribution(pool, boost_info))
+    }
+}
+
+// The participation of a voter over the window.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Participation {
+    pub num_votes: u64,
+    pub longest_streak: u64,
+}
+
+impl Participation {
+    // Weight of the voter in the distribution: one per proposal voted on, plus the streak bonus.
+    pub fn weight(&self, streak_bonus: Option<f64>) -> f64 {
+        let bonus = streak_bonus.unwrap_or(0.0) * self.longest_streak.saturating_sub(1) as f64;
+        self.num_votes as f64 + bonus
+    }
+}
+
+// LRU cache that uses `boost_id` and `chain_id` as keys
+#[cached(
+    result = true,
+    sync_writes = true,
+    type = "TimedSizedCache<String, Distribution>",
+    create = "{ TimedSizedCache::with_size_and_lifespan(100, 3 * WEEK.as_secs()) }",
+    convert = r#"{ format!("{}{}", boost_info.id, boost_info.chain_id) }"#
+)]
+pub async fn cached_space_distribution(
+    pool: &mysql_async::Pool,
+    boost_info: &BoostInfo,
+) -> Result<Distribution, ServerError> {
+    let params = boost_info
+        .params
+        .space
+        .as_ref()
+        .ok_or("boost is not a space boost")?;
+    validate_window_end(params.end)?;
+
+    let proposals = get_space_proposals(pool, params).await?;
+    tracing::info!(space = params.space, num_proposals = proposals.len());
+    let participations = get_participations(pool, params, &proposals).await?;
+
+    let mut votes = participations
+        .into_iter()
+        .map(|(voter, participation)| Vote {
+            voter,
+            voting_power: participation.weight(params.streak_bonus),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    // Highest weight first, as for proposal boosts. Ties are broken by address so that the order never changes.
+    votes.sort_by(|a, b| {
+        b.voting_power
+            .total_cmp(&a.voting_power)
+            .then(a.voter.cmp(&b.voter))
+    });
+
+    compute_distribution(votes, boost_info, 0.0)
+}
+
+fn validate_window_end(end: u64) -> Result<(), ServerError> {
+    let current_timestamp = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap() // Safe to unwrap because we are sure that the current time is after the UNIX_EPOCH
+        .as_secs();
+    if current_timestamp < end {
+        Err(ServerError::ErrorString(
+            "space boost window has not ended yet".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+// The proposals of the space that ran within the window. Only the proposals with final scores count, as for
+// proposal boosts.
+const WINDOW_PROPOSALS: &str = "WHERE space = :space
+    AND start >= :start
+    AND end <= :end
+    AND scores_state = 'final'";
+
+// Returns the ids of the proposals of the space that ran within the window, ordered by start.
+async fn get_space_proposals(
+    pool: &mysql_async::Pool,
+    params: &SpaceParams,
+) -> Result<Vec<String>, ServerError> {
+    let query = format!(
+        "SELECT id
+        FROM proposals
+        {}
+        ORDER BY start ASC, id ASC;",
+        WINDOW_PROPOSALS
+    );
+
+    let mut conn = pool.get_conn().await?;
+    let proposals: Vec<String> = conn
+        .exec(
+            query,
+            params! {
+                "space" => &params.space,
+                "start" => params.start,
+                "end" => params.end,
+            },
+        )
+        .await?;
+    conn.disconnect().await?;
+
+    Ok(proposals)
+}
+
+// Aggregates the votes of every voter over the proposals of the window, `proposals` being their ids ordered by start.
+// Streaks need to know which proposals every voter voted on, otherwise only the number of votes is fetched.
+async fn get_participations(
+    pool: &mysql_async::Pool,
+    params: &SpaceParams,
+    proposals: &[String],
+) -> Result<HashMap<Address, Participation>, ServerError> {
+    if proposals.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // The proposals are selected by a join rather than an `IN` list, which could exceed the placeholder limit of MySQL
+    let window = params! {
+        "space" => &params.space,
+        "start" => params.start,
+        "end" => params.end,
+    };
+
+    let mut conn = pool.get_conn().await?;
+    let mut participations = HashMap::new();
+    if params.streak_bonus.is_some() {
+        let query = format!(
+            "SELECT votes.voter, votes.proposal
+            FROM votes
+            JOIN (SELECT id FROM proposals {}) AS window_proposals
+            ON votes.proposal = window_proposals.id;",
+            WINDOW_PROPOSALS
+        );
+        let result: Vec<(String, String)> = conn.exec(query, window).await?;
+
+        let index: HashMap<&str, usize> = proposals
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.as_str(), i))
+            .collect();
+        let mut voted: HashMap<Address, Vec<usize>> = HashMap::new();
+        for (voter, proposal) in result {
+            if let Some(i) = index.get(proposal.as_str()) {
+                voted
+                    .entry(Address::from_str(&voter)?)
+                    .or_default()
+                    .push(*i);
+            }
+        }
+        for (voter, indexes) in voted {
+            participations.insert(voter, participation_from_indexes(indexes));
+        }
+    } else {
+        let query = format!(
+            "SELECT votes.voter, COUNT(*) AS num_votes
+            FROM votes
+            JOIN (SELECT id FROM proposals {}) AS window_proposals
+            ON votes.proposal = window_proposals.id
+            GROUP BY votes.voter;",
+            WINDOW_PROPOSALS
+        );
+        let result: Vec<(String, u64)> = conn.exec(query, window).await?;
+        for (voter, num_votes) in result {
+            participations.insert(
+                Address::from_str(&voter)?,
+                Participation {
+                    num_votes,
+                    longest_streak: 0,
+                },
+            );
+        }
+    }
+    conn.disconnect().await?;
+
+    Ok(participations)
+}
+
+// `indexes` are the positions, in the window, of the proposals the voter voted on.
+fn participation_from_indexes(mut indexes: Vec<usize>) -> Participation {
+    indexes.sort_unstable();
+    indexes.dedup();
+
+    let mut longest_streak = 0;
+    let mut streak = 0;
+    let mut previous = None;
+    for i in &indexes {
+        streak = match previous {
+            Some(p) if p + 1 == *i => streak + 1,
+            _ => 1,
+        };
+        longest_streak = longest_streak.max(streak);
+        previous = Some(*i);
+    }
+
+    Participation {
+        num_votes: indexes.len() as u64,
+        longest_streak,
+    }
+}
+
+#[cfg(test)]
+mod test_space {
+    use super::{participation_from_indexes, Participation, SpaceBoost, SpaceParams};
+    use crate::routes::test_helpers::query_strategy;
+    use crate::routes::DistributionType;
+    use crate::strategy::RewardStrategy;
+
+    #[test]
+    fn test_parse() {
+        let params =
+            SpaceParams::parse(Some("snapshot.eth"), Some("100"), Some("200"), Some("0.5"))
+                .unwrap();
+        assert_eq!(
+            params,
+            SpaceParams {
+                space: "snapshot.eth".to_string(),
+                start: 100,
+                end: 200,
+                streak_bonus: Some(0.5),
+            }
+        );
+
+        assert!(SpaceParams::parse(None, Some("100"), Some("200"), None).is_err());
+        assert!(SpaceParams::parse(Some("snapshot.eth"), Some("200"), Some("100"), None).is_err());
+        assert!(
+            SpaceParams::parse(Some("snapshot.eth"), Some("100"), Some("200"), Some("-1")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_space_id() {
+        for space in ["snapshot.eth", "s:snapshot.eth", "my-space_1.eth"] {
+            assert!(SpaceParams::parse(Some(space), Some("100"), Some("200"), None).is_ok());
+        }
+        // Space ids end up in SQL queries
+        for space in [
+            "",
+            "snapshot.eth' OR '1'='1",
+            "snapshot eth",
+            "snapshot.eth;",
+        ] {
+            assert_eq!(
+                SpaceParams::parse(Some(space), Some("100"), Some("200"), None),
+                Err("invalid space")
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_boost() {
+        let strategy = || {
+            let mut strategy = query_strategy("space");
+            strategy.space = Some("snapshot.eth".to_string());
+            strategy.window_start = Some("100".to_string());
+            strategy.window_end = Some("200".to_string());
+            strategy
+        };
+        let params = SpaceBoost.parse(strategy()).unwrap();
+        assert!(matches!(
+            params.distribution,
+            DistributionType::Weighted(None)
+        ));
+
+        // Every voter would get the same reward, whatever their participation
+        let mut even = strategy();
+        even.distribution.type_ = "even".to_string();
+        assert_eq!(
+            SpaceBoost.parse(even).unwrap_err(),
+            "space boosts only support weighted distributions"
+        );
+
+        // Options of proposal boosts are rejected rather than silently ignored
+        let mut min_voting_power = strategy();
+        min_voting_power.eligibility.min_voting_power = Some("10".to_string());
+        assert_eq!(
+            SpaceBoost.parse(min_voting_power).unwrap_err(),
+            "space boosts don't support eligibility options"
+        );
+        let mut denylist = strategy();
+        denylist.eligibility.denylist = Some(vec![]);
+        assert!(SpaceBoost.parse(denylist).is_err());
+        let mut passthrough = strategy();
+        passthrough.distribution.delegator_passthrough = Some(true);
+        assert_eq!(
+            SpaceBoost.parse(passthrough).unwrap_err(),
+            "space boosts don't support distribution options"
+        );
+    }
+
+    #[test]
+    fn test_streaks() {
+        assert_eq!(
+            participation_from_indexes(vec![4, 0, 1, 2, 2]),
+            Participation {
+                num_votes: 4,
+                longest_streak: 3,
+            }
+        );
+        assert_eq!(
+            participation_from_indexes(vec![0, 2, 4]),
+            Participation {
+                num_votes: 3,
+                longest_streak: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_weight() {
+        let participation = Participation {
+            num_votes: 4,
+            longest_streak: 3,
+        };
+        assert_eq!(participation.weight(None), 4.0);
+        assert_eq!(participation.weight(Some(0.5)), 5.0);
+    }
+}